@@ -1,5 +1,3 @@
-use std::time::Instant;
-
 use rand::{Rng, SeedableRng};
 use rand::rngs::SmallRng;
 use serde::Deserialize;
@@ -7,6 +5,7 @@ use serde::Deserialize;
 use rusty_simanneal::{
     AnnealingState, AnnealingStateBack, AnnealingStatePeeking, EnergyMeasurable, Transition,
 };
+use rusty_simanneal::metrics::MetricsConfig;
 use rusty_simanneal::schedule::LinearStepSchedule;
 
 // from simanneal import Annealer
@@ -42,6 +41,7 @@ use rusty_simanneal::schedule::LinearStepSchedule;
 #[derive(Deserialize, Debug, Clone)]
 struct Row {
     #[serde(rename = "Town")]
+    #[allow(dead_code)]
     town: String,
     #[serde(rename = "Longitude")]
     longitude: f64,
@@ -156,7 +156,7 @@ impl Transition for TspTransition {
     type Context = TspContext;
     type State = TspState;
 
-    fn choose<G: Rng>(rng: &mut G, ctx: &Self::Context, state: &Self::State) -> Self {
+    fn choose<G: Rng>(rng: &mut G, _ctx: &Self::Context, state: &Self::State) -> Self {
         let a = rng.gen_range(0..state.route.len());
         let b = rng.gen_range(0..state.route.len());
         Self { a, b }
@@ -164,7 +164,7 @@ impl Transition for TspTransition {
 }
 
 fn main() {
-    let mut reader = include_bytes!("data/location.txt").to_vec();
+    let reader = include_bytes!("data/location.txt").to_vec();
     let rdr = csv::ReaderBuilder::new().from_reader(&reader[..]);
 
     let rows: Result<Vec<_>, _> = rdr.into_deserialize::<Row>().collect();
@@ -181,7 +181,7 @@ fn main() {
 
     let ctx = TspContext { distance_matrix };
     let state = TspState {
-        route: (0..rows.len()).collect::<Vec<_>>().try_into().unwrap(),
+        route: (0..rows.len()).collect::<Vec<_>>(),
     };
 
     {
@@ -194,12 +194,15 @@ fn main() {
             LinearStepSchedule::new(100.0, 0.01, 10_000_000),
         );
 
-        let start = Instant::now();
-        let best_state = annealer.anneal::<_, false>(&mut rng);
+        let result = annealer.anneal::<_, false>(&mut rng);
 
-        println!("process time {}ms", start.elapsed().as_millis());
-        println!("{:?}", best_state);
-        println!("{:?}", best_state.energy(&ctx));
+        println!("process time {}ms", result.elapsed.as_millis());
+        println!("{:?}", result.best_state);
+        println!("{:?}", result.best_energy);
+        println!(
+            "accepted {} rejected {} invalid {}",
+            result.accepted, result.rejected, result.invalid
+        );
     }
 
     {
@@ -212,12 +215,15 @@ fn main() {
             LinearStepSchedule::new(100.0, 0.01, 10_000_000),
         );
 
-        let start = Instant::now();
-        let best_state = annealer.anneal_back::<_, false>(&mut rng);
+        let result = annealer.anneal_back::<_, false>(&mut rng);
 
-        println!("process time {}ms", start.elapsed().as_millis());
-        println!("{:?}", best_state);
-        println!("{:?}", best_state.energy(&ctx));
+        println!("process time {}ms", result.elapsed.as_millis());
+        println!("{:?}", result.best_state);
+        println!("{:?}", result.best_energy);
+        println!(
+            "accepted {} rejected {} invalid {}",
+            result.accepted, result.rejected, result.invalid
+        );
     }
 
     {
@@ -228,14 +234,28 @@ fn main() {
             state,
             ctx.clone(),
             LinearStepSchedule::new(100.0, 0.01, 10_000_000),
-        );
+        )
+        .with_metrics_config(MetricsConfig::PerTemperature(4));
 
-        let start = Instant::now();
-        let best_state = annealer.anneal_peek::<_, true>(&mut rng);
+        let result = annealer.anneal_peek::<_, true>(&mut rng);
 
-        println!("process time {}ms", start.elapsed().as_millis());
-        println!("{:?}", best_state);
-        println!("{:?}", best_state.energy(&ctx));
+        println!("process time {}ms", result.elapsed.as_millis());
+        println!("{:?}", result.best_state);
+        println!("{:?}", result.best_energy);
+        println!(
+            "accepted {} rejected {} invalid {}",
+            result.accepted, result.rejected, result.invalid
+        );
+        for bucket in &annealer.metrics_buckets {
+            println!(
+                "temperature {:.3}..{:.3} acceptance {:.3} mean delta {:.3} best {:.3}",
+                bucket.temperature_min,
+                bucket.temperature_max,
+                bucket.acceptance_rate(),
+                bucket.mean_delta(),
+                bucket.best_energy
+            );
+        }
     }
 }
 
@@ -263,7 +283,7 @@ mod tests {
     }
 
     fn init_data() -> (TspContext, TspState) {
-        let mut reader = include_bytes!("data/location.txt").to_vec();
+        let reader = include_bytes!("data/location.txt").to_vec();
         let rdr = csv::ReaderBuilder::new().from_reader(&reader[..]);
 
         let rows: Result<Vec<_>, _> = rdr.into_deserialize::<Row>().collect();
@@ -280,7 +300,7 @@ mod tests {
 
         let ctx = TspContext { distance_matrix };
         let state = TspState {
-            route: (0..rows.len()).collect::<Vec<_>>().try_into().unwrap(),
+            route: (0..rows.len()).collect::<Vec<_>>(),
         };
 
         (ctx, state)
@@ -71,7 +71,7 @@ fn main() {
         schedule::LinearStepSchedule::new(1000.0, 0.01, 10000),
     );
 
-    let state = annealer.anneal::<_, false>(&mut rand::thread_rng());
+    let state = annealer.anneal::<_, false>(&mut rand::thread_rng()).best_state;
 
     let QuadraticFunction { a, b, .. } = annealer.ctx;
     let answer = -b / (2.0 * a);
@@ -0,0 +1,262 @@
+use rand::Rng;
+
+/// Acceptance is a criterion deciding whether a move to a new energy is accepted.
+/// Energies are costs to minimize (the energy, negated when maximizing) relative to the initial energy
+/// of the run, so that they stay small for large integer energies. `delta` is the change of the cost
+/// by the move, computed exactly with `Energy::delta`, and the new energy is `current_energy + delta`.
+/// `temperature` is the current temperature of the schedule.
+pub trait Acceptance {
+    /// Called at the beginning of annealing and on restarts with the energy of the state.
+    fn init(&mut self, _energy: f64) {}
+
+    fn accept<G: Rng>(
+        &mut self,
+        rng: &mut G,
+        current_energy: f64,
+        delta: f64,
+        best_energy: f64,
+        temperature: f64,
+    ) -> bool;
+}
+
+/// Accept with probability min(1, exp(-delta / T)).
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metropolis;
+
+impl Acceptance for Metropolis {
+    fn accept<G: Rng>(
+        &mut self,
+        rng: &mut G,
+        _current_energy: f64,
+        delta: f64,
+        _best_energy: f64,
+        temperature: f64,
+    ) -> bool {
+        let p = rng.gen_range(0.0..=1.0);
+        !(delta.is_sign_positive() && (-delta / temperature).exp() < p)
+    }
+}
+
+/// Accept with probability 1 / (1 + exp(delta / T)).
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Barker;
+
+impl Acceptance for Barker {
+    fn accept<G: Rng>(
+        &mut self,
+        rng: &mut G,
+        _current_energy: f64,
+        delta: f64,
+        _best_energy: f64,
+        temperature: f64,
+    ) -> bool {
+        let p = rng.gen_range(0.0..=1.0);
+        p < 1.0 / (1.0 + (delta / temperature).exp())
+    }
+}
+
+/// Threshold accepting: accept deterministically when delta < T.
+/// The temperature of the schedule is used as the threshold.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Threshold;
+
+impl Acceptance for Threshold {
+    fn accept<G: Rng>(
+        &mut self,
+        _rng: &mut G,
+        _current_energy: f64,
+        delta: f64,
+        _best_energy: f64,
+        temperature: f64,
+    ) -> bool {
+        delta < temperature
+    }
+}
+
+/// Great deluge: accept when the new energy is below the water level.
+/// The level starts at the initial energy and is lowered by `rain_speed` on each acceptance.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GreatDeluge {
+    pub rain_speed: f64,
+    pub level: f64,
+}
+
+impl GreatDeluge {
+    pub fn new(rain_speed: f64) -> Self {
+        Self {
+            rain_speed,
+            level: f64::INFINITY,
+        }
+    }
+}
+
+impl Acceptance for GreatDeluge {
+    fn init(&mut self, energy: f64) {
+        self.level = energy;
+    }
+
+    fn accept<G: Rng>(
+        &mut self,
+        _rng: &mut G,
+        current_energy: f64,
+        delta: f64,
+        _best_energy: f64,
+        _temperature: f64,
+    ) -> bool {
+        if current_energy + delta <= self.level {
+            self.level -= self.rain_speed;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Record-to-record travel: accept when the new energy is within `deviation` of the best energy.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecordToRecord {
+    pub deviation: f64,
+}
+
+impl RecordToRecord {
+    pub fn new(deviation: f64) -> Self {
+        Self { deviation }
+    }
+}
+
+impl Acceptance for RecordToRecord {
+    fn accept<G: Rng>(
+        &mut self,
+        _rng: &mut G,
+        current_energy: f64,
+        delta: f64,
+        best_energy: f64,
+        _temperature: f64,
+    ) -> bool {
+        current_energy + delta <= best_energy + self.deviation
+    }
+}
+
+/// Late acceptance hill climbing (LAHC): accept when the new energy is not worse than
+/// the current energy or the energy `history.len()` steps ago. The temperature is ignored.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LateAcceptance {
+    pub history: Vec<f64>,
+    pub step: usize,
+}
+
+impl LateAcceptance {
+    pub fn new(history_length: usize) -> Self {
+        assert!(history_length > 0, "history_length must be positive");
+        Self {
+            history: vec![f64::INFINITY; history_length],
+            step: 0,
+        }
+    }
+}
+
+impl Acceptance for LateAcceptance {
+    fn init(&mut self, energy: f64) {
+        self.history.fill(energy);
+        self.step = 0;
+    }
+
+    fn accept<G: Rng>(
+        &mut self,
+        _rng: &mut G,
+        current_energy: f64,
+        delta: f64,
+        _best_energy: f64,
+        _temperature: f64,
+    ) -> bool {
+        let new_energy = current_energy + delta;
+        let v = self.step % self.history.len();
+        let accept = new_energy <= self.history[v] || delta <= 0.0;
+        self.history[v] = if accept { new_energy } else { current_energy };
+        self.step += 1;
+        accept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn acceptance_rate<A: Acceptance>(acceptance: &mut A, delta: f64, temperature: f64) -> f64 {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let accepted = (0..10000)
+            .filter(|_| acceptance.accept(&mut rng, 0.0, delta, 0.0, temperature))
+            .count();
+        accepted as f64 / 10000.0
+    }
+
+    #[test]
+    fn metropolis() {
+        assert_eq!(acceptance_rate(&mut Metropolis, -1.0, 1.0), 1.0);
+        let rate = acceptance_rate(&mut Metropolis, 1.0, 1.0);
+        assert!((rate - (-1.0f64).exp()).abs() < 0.02, "rate: {}", rate);
+    }
+
+    #[test]
+    fn barker() {
+        let rate = acceptance_rate(&mut Barker, 0.0, 1.0);
+        assert!((rate - 0.5).abs() < 0.02, "rate: {}", rate);
+        let rate = acceptance_rate(&mut Barker, 1.0, 1.0);
+        assert!(
+            (rate - 1.0 / (1.0 + 1.0f64.exp())).abs() < 0.02,
+            "rate: {}",
+            rate
+        );
+    }
+
+    #[test]
+    fn threshold() {
+        assert_eq!(acceptance_rate(&mut Threshold, 0.5, 1.0), 1.0);
+        assert_eq!(acceptance_rate(&mut Threshold, 1.5, 1.0), 0.0);
+    }
+
+    #[test]
+    fn great_deluge() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut acceptance = GreatDeluge::new(1.0);
+        acceptance.init(10.0);
+
+        assert!(acceptance.accept(&mut rng, 10.0, 0.0, 10.0, 0.0));
+        assert_eq!(acceptance.level, 9.0);
+        assert!(!acceptance.accept(&mut rng, 10.0, -0.5, 10.0, 0.0));
+        assert_eq!(acceptance.level, 9.0);
+    }
+
+    #[test]
+    fn record_to_record() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut acceptance = RecordToRecord::new(1.0);
+
+        assert!(acceptance.accept(&mut rng, 0.0, 5.5, 5.0, 0.0));
+        assert!(!acceptance.accept(&mut rng, 0.0, 6.5, 5.0, 0.0));
+    }
+
+    #[test]
+    fn late_acceptance() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut acceptance = LateAcceptance::new(2);
+        acceptance.init(10.0);
+
+        // worse than current but not worse than 2 steps ago
+        assert!(acceptance.accept(&mut rng, 8.0, 1.0, 8.0, 0.0));
+        // worse than both current and 2 steps ago
+        assert!(!acceptance.accept(&mut rng, 9.0, 2.0, 8.0, 0.0));
+        // history[0] is now 9.0
+        assert!(!acceptance.accept(&mut rng, 5.0, 4.5, 5.0, 0.0));
+        assert_eq!(acceptance.history, vec![5.0, 9.0]);
+    }
+}
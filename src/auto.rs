@@ -0,0 +1,231 @@
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::acceptance::{Acceptance, Metropolis};
+use crate::energy::Energy;
+use crate::moves::MoveGenerator;
+use crate::schedule::{ExponentialStepSchedule, Schedule};
+use crate::{Annealer, AnnealingState};
+
+/// Acceptance rate which the calibrated t_max should give.
+const T_MAX_ACCEPTANCE: f64 = 0.98;
+/// Factor by which the temperature is changed between exploratory walks.
+const T_FACTOR: f64 = 1.5;
+/// Upper bound of exploratory walks in each search so that flat landscapes terminate.
+const MAX_ROUNDS: usize = 100;
+
+impl<S: AnnealingState, C: Schedule, A: Acceptance, N: MoveGenerator<S>> Annealer<S, C, A, N> {
+    /// Calibrate an exponential schedule like simanneal's `auto()`.
+    ///
+    /// Short exploratory walks of `steps` steps are run from a copy of the current state.
+    /// t_max is the temperature giving ~98% acceptance, t_min is the temperature at which no walk improves the energy anymore,
+    /// and max_steps is the number of steps estimated to take `duration`.
+    /// The walks always use the Metropolis criterion and choose their moves with the move generator of the annealer,
+    /// without giving it feedback so that they do not adapt it. The state of the annealer is left untouched.
+    /// Panics when `steps` is 0.
+    pub fn auto_schedule<G: Rng>(
+        &mut self,
+        rng: &mut G,
+        duration: Duration,
+        steps: usize,
+    ) -> ExponentialStepSchedule {
+        assert!(steps > 0, "steps must be positive");
+        let start = Instant::now();
+        let mut state = self.state.clone();
+        let mut step = 0;
+
+        // find an initial guess for temperature
+        let mut temperature = 0.0;
+        let energy = state.energy(&self.ctx);
+        while temperature == 0.0 && step < steps {
+            step += 1;
+            let mut next = state.clone();
+            let op = self.moves.choose(rng, &self.ctx, &state);
+            if next.apply(&self.ctx, &op).is_some() {
+                temperature = next.energy(&self.ctx).delta(energy).abs();
+            }
+        }
+        if temperature == 0.0 {
+            temperature = 1.0;
+        }
+
+        // search for t_max, a temperature that gives 98% acceptance
+        let (mut acceptance, mut improvement) = self.explore(rng, &mut state, temperature, steps);
+        step += steps;
+        let mut rounds = 0;
+        while acceptance > T_MAX_ACCEPTANCE && rounds < MAX_ROUNDS {
+            temperature = round_figures(temperature / T_FACTOR, 2);
+            (acceptance, improvement) = self.explore(rng, &mut state, temperature, steps);
+            step += steps;
+            rounds += 1;
+        }
+        rounds = 0;
+        while acceptance < T_MAX_ACCEPTANCE && rounds < MAX_ROUNDS {
+            temperature = round_figures(temperature * T_FACTOR, 2);
+            (acceptance, improvement) = self.explore(rng, &mut state, temperature, steps);
+            step += steps;
+            rounds += 1;
+        }
+        let t_max = temperature;
+
+        // search for t_min, a temperature that gives 0% improvement
+        rounds = 0;
+        while improvement > 0.0 && rounds < MAX_ROUNDS {
+            temperature = round_figures(temperature / T_FACTOR, 2);
+            (_, improvement) = self.explore(rng, &mut state, temperature, steps);
+            step += steps;
+            rounds += 1;
+        }
+        let t_min = temperature;
+
+        // calculate anneal duration
+        let elapsed = start.elapsed().as_secs_f64();
+        let max_steps = round_figures(duration.as_secs_f64() * step as f64 / elapsed, 2) as usize;
+
+        ExponentialStepSchedule::new(t_max, t_min, max_steps)
+    }
+
+    /// Run `steps` steps at a fixed temperature and return the acceptance and improvement rates.
+    fn explore<G: Rng>(
+        &mut self,
+        rng: &mut G,
+        state: &mut S,
+        temperature: f64,
+        steps: usize,
+    ) -> (f64, f64) {
+        let mut current_energy = state.energy(&self.ctx);
+        let mut accepts = 0;
+        let mut improves = 0;
+
+        for _ in 0..steps {
+            let prev_state = state.clone();
+            let op = self.moves.choose(rng, &self.ctx, state);
+            if state.apply(&self.ctx, &op).is_none() {
+                continue;
+            }
+            let new_energy = state.energy(&self.ctx);
+            let delta = self.direction.cost_delta(new_energy, current_energy);
+            if !Metropolis.accept(rng, 0.0, delta, 0.0, temperature) {
+                *state = prev_state;
+            } else {
+                accepts += 1;
+                if delta < 0.0 {
+                    improves += 1;
+                }
+                current_energy = new_energy;
+            }
+        }
+
+        let steps = steps as f64;
+        (accepts as f64 / steps, improves as f64 / steps)
+    }
+}
+
+/// Round a number to the given number of significant figures.
+fn round_figures(x: f64, figures: i32) -> f64 {
+    if x == 0.0 {
+        return 0.0;
+    }
+    let scale = 10f64.powi(figures - 1 - x.abs().log10().floor() as i32);
+    (x * scale).round() / scale
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::tests::{
+        quadratic_annealer, QuadraticFunction, QuadraticFunctionState, QuadraticFunctionTransition,
+    };
+
+    #[test]
+    fn round_to_significant_figures() {
+        assert_eq!(round_figures(1234.0, 2), 1200.0);
+        assert_eq!(round_figures(0.012345, 2), 0.012);
+        assert_eq!(round_figures(0.0, 2), 0.0);
+    }
+
+    #[test]
+    fn auto_calibrated_schedule_solves_quadratic_function() {
+        let ctx = QuadraticFunction {
+            a: 1.0,
+            b: 10.0,
+            c: 30.0,
+        };
+        let mut annealer = Annealer::new(
+            QuadraticFunctionState { x: 100.0 },
+            ctx.clone(),
+            crate::schedule::LinearStepSchedule::new(1.0, 0.0, 0),
+        );
+
+        let schedule =
+            annealer.auto_schedule(&mut rand::thread_rng(), Duration::from_millis(50), 200);
+        assert!(
+            schedule.t_max > schedule.t_min,
+            "{} {}",
+            schedule.t_max,
+            schedule.t_min
+        );
+        assert!(schedule.t_min > 0.0);
+        assert!(schedule.max_steps > 0);
+
+        let mut annealer = Annealer::new(QuadraticFunctionState { x: 100.0 }, ctx, schedule);
+        let state = annealer
+            .anneal::<_, false>(&mut rand::thread_rng())
+            .best_state;
+        assert!((state.x - (-5.0)).abs() < 0.1, "{:?}", state);
+    }
+
+    #[test]
+    #[should_panic(expected = "steps must be positive")]
+    fn zero_steps() {
+        let mut annealer =
+            quadratic_annealer(crate::schedule::LinearStepSchedule::new(1.0, 0.0, 0));
+        annealer.auto_schedule(&mut rand::thread_rng(), Duration::from_millis(10), 0);
+    }
+
+    /// Steps a hundred times larger than those of `Transition::choose`.
+    struct LargeSteps;
+
+    impl MoveGenerator<QuadraticFunctionState> for LargeSteps {
+        fn choose<G: Rng>(
+            &mut self,
+            rng: &mut G,
+            _ctx: &QuadraticFunction,
+            _state: &QuadraticFunctionState,
+        ) -> QuadraticFunctionTransition {
+            QuadraticFunctionTransition::Add(rng.gen_range(-1000.0..=1000.0))
+        }
+    }
+
+    #[test]
+    fn calibrates_with_the_move_generator() {
+        let annealer = || {
+            Annealer::new(
+                QuadraticFunctionState { x: 100.0 },
+                QuadraticFunction {
+                    a: 1.0,
+                    b: 10.0,
+                    c: 30.0,
+                },
+                crate::schedule::LinearStepSchedule::new(1.0, 0.0, 0),
+            )
+        };
+        let rng = || SmallRng::seed_from_u64(0);
+        let duration = Duration::from_millis(10);
+
+        let small = annealer().auto_schedule(&mut rng(), duration, 200);
+        let large = annealer()
+            .with_moves(LargeSteps)
+            .auto_schedule(&mut rng(), duration, 200);
+        assert!(
+            large.t_max > 100.0 * small.t_max,
+            "{} {}",
+            large.t_max,
+            small.t_max
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use std::time::Duration;
+
+use crate::moves::TransitionMoves;
+use crate::restart::Restarts;
+use crate::schedule::Schedule;
+use crate::EnergyMeasurable;
+
+/// Snapshot of an `AnnealRun` taken with `AnnealRun::checkpoint` and continued with `Annealer::resume`.
+/// With a seedable random number generator the resumed run follows exactly the same trajectory
+/// as the uninterrupted one. The move generator is included and restored with `MoveGenerator::restore`.
+/// Stop conditions, observers and the restart policy are not included,
+/// the resuming annealer is expected to be built with the same ones.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint<S: EnergyMeasurable, C: Schedule, A, G, N = TransitionMoves> {
+    pub progress: C::Progress,
+    /// Number of steps done
+    pub step: usize,
+    /// Step of the last improvement of the best energy
+    pub last_improvement: usize,
+    pub accepted: usize,
+    pub rejected: usize,
+    pub invalid: usize,
+    pub elapsed: Duration,
+    pub state: S,
+    pub current_energy: S::Energy,
+    /// Energy at the start of the run, the reference of the energies given to the acceptance criterion
+    pub initial_energy: S::Energy,
+    pub best_state: S,
+    pub best_energy: S::Energy,
+    pub schedule: C,
+    pub acceptance: A,
+    pub rng: G,
+    pub moves: N,
+    pub(crate) restarts: Restarts,
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use crate::acceptance::LateAcceptance;
+    use crate::metrics::MetricsConfig;
+    use crate::run::{BackMode, CloneMode};
+    use crate::schedule::AdaptiveStepSchedule;
+    use crate::tests::{quadratic_annealer, QuadraticFunctionState};
+    use crate::Annealer;
+
+    fn annealer() -> Annealer<QuadraticFunctionState, AdaptiveStepSchedule, LateAcceptance> {
+        quadratic_annealer(AdaptiveStepSchedule::new(100.0, 0.5, 0.01, 2000))
+            .with_acceptance(LateAcceptance::new(10))
+    }
+
+    fn energies(
+        annealer: &Annealer<QuadraticFunctionState, AdaptiveStepSchedule, LateAcceptance>,
+    ) -> Vec<f64> {
+        annealer.metrics.iter().map(|m| m.current_energy).collect()
+    }
+
+    #[test]
+    fn resume_follows_the_same_trajectory() {
+        let mut uninterrupted = annealer();
+        let expected = uninterrupted
+            .anneal_back::<_, true>(&mut SmallRng::seed_from_u64(0))
+            .best_state;
+
+        let mut interrupted = annealer();
+        let mut run = interrupted.start_back::<_, true>(SmallRng::seed_from_u64(0));
+        run.run_for(700);
+        let checkpoint = run.checkpoint();
+        assert_eq!(checkpoint.step, 700);
+
+        let mut resumed = annealer();
+        let best_state = resumed
+            .resume::<BackMode, _, true>(checkpoint)
+            .finish()
+            .best_state;
+
+        assert_eq!(best_state.x, expected.x);
+        assert_eq!(energies(&resumed), energies(&uninterrupted)[700..]);
+    }
+
+    #[test]
+    fn resume_keeps_last_metrics() {
+        let mut uninterrupted = annealer().with_metrics_config(MetricsConfig::Last(3));
+        let steps = uninterrupted
+            .anneal::<_, true>(&mut SmallRng::seed_from_u64(0))
+            .steps;
+
+        let mut interrupted = annealer();
+        let mut run = interrupted.start::<_, false>(SmallRng::seed_from_u64(0));
+        run.run_for(1000);
+        let checkpoint = run.checkpoint();
+
+        let mut resumed = annealer().with_metrics_config(MetricsConfig::Last(3));
+        resumed.resume::<CloneMode, _, true>(checkpoint).finish();
+
+        let recorded: Vec<_> = resumed.metrics.iter().map(|m| m.step).collect();
+        assert_eq!(recorded, [steps - 2, steps - 1, steps]);
+        assert_eq!(energies(&resumed), energies(&uninterrupted));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn resume_from_json() {
+        use rand_chacha::ChaCha8Rng;
+
+        use crate::checkpoint::Checkpoint;
+        use crate::schedule::Schedule;
+
+        let mut uninterrupted = annealer();
+        let expected = uninterrupted
+            .anneal::<_, false>(&mut ChaCha8Rng::seed_from_u64(0))
+            .best_state;
+
+        let mut interrupted = annealer();
+        let mut run = interrupted.start::<_, false>(ChaCha8Rng::seed_from_u64(0));
+        run.run_for(1000);
+        let json = serde_json::to_string(&run.checkpoint()).unwrap();
+
+        let checkpoint: Checkpoint<_, AdaptiveStepSchedule, LateAcceptance, ChaCha8Rng> =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(checkpoint.schedule.progress_0_1(&checkpoint.progress), 0.5);
+        let mut resumed = annealer();
+        let best_state: QuadraticFunctionState = resumed
+            .resume::<CloneMode, _, false>(checkpoint)
+            .finish()
+            .best_state;
+
+        assert_eq!(best_state.x, expected.x);
+    }
+}
@@ -0,0 +1,564 @@
+use rand::{Rng, RngCore};
+
+use crate::moves::{MoveGenerator, MoveOutcome};
+use crate::AnnealingState;
+
+/// Object safe form of `MoveGenerator` so that `CompositeMoves` can hold generators of different types.
+/// Implemented for every `MoveGenerator` which is `Clone`.
+pub trait DynMoveGenerator<S: AnnealingState> {
+    fn choose_dyn(&mut self, rng: &mut dyn RngCore, ctx: &S::Context, state: &S) -> S::Transition;
+
+    fn feedback_dyn(&mut self, op: &S::Transition, outcome: MoveOutcome);
+
+    fn clone_box(&self) -> Box<dyn DynMoveGenerator<S>>;
+}
+
+impl<S: AnnealingState, N: MoveGenerator<S> + Clone + 'static> DynMoveGenerator<S> for N {
+    fn choose_dyn(
+        &mut self,
+        mut rng: &mut dyn RngCore,
+        ctx: &S::Context,
+        state: &S,
+    ) -> S::Transition {
+        self.choose(&mut rng, ctx, state)
+    }
+
+    fn feedback_dyn(&mut self, op: &S::Transition, outcome: MoveOutcome) {
+        self.feedback(op, outcome)
+    }
+
+    fn clone_box(&self) -> Box<dyn DynMoveGenerator<S>> {
+        Box::new(self.clone())
+    }
+}
+
+impl<S: AnnealingState> Clone for Box<dyn DynMoveGenerator<S>> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Move generator calling a function of the random number generator, the context and the state.
+#[derive(Clone)]
+struct FnMoves<F>(F);
+
+impl<S, F> MoveGenerator<S> for FnMoves<F>
+where
+    S: AnnealingState,
+    F: FnMut(&mut dyn RngCore, &S::Context, &S) -> S::Transition,
+{
+    fn choose<G: Rng>(&mut self, rng: &mut G, ctx: &S::Context, state: &S) -> S::Transition {
+        (self.0)(rng, ctx, state)
+    }
+}
+
+/// A kind of move of `CompositeMoves`.
+#[derive(Clone)]
+pub struct Operator<S: AnnealingState> {
+    pub name: &'static str,
+    /// Relative probability of choosing the operator before any adaptation
+    pub weight: f64,
+    /// Generates the moves of the operator and gets the feedback on them
+    pub generator: Box<dyn DynMoveGenerator<S>>,
+}
+
+/// Acceptance statistics of an operator.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OperatorStats {
+    /// Number of moves chosen by the operator
+    pub attempts: usize,
+    pub accepts: usize,
+    /// Accepted moves which decreased the cost
+    pub improvements: usize,
+    pub new_bests: usize,
+    pub invalid: usize,
+}
+
+impl OperatorStats {
+    /// Accepted moves per attempted move, 0 when the operator was never chosen
+    pub fn acceptance_rate(&self) -> f64 {
+        if self.attempts == 0 {
+            return 0.0;
+        }
+        self.accepts as f64 / self.attempts as f64
+    }
+}
+
+/// How `CompositeMoves` adapts the probabilities of the operators while annealing.
+/// The adaptive rules are probability matching: each operator keeps a quality, an exponential moving
+/// average of its rewards with rate `learning_rate`, and is chosen with probability
+/// p_min + (1 - K p_min) q / sum(q) where K is the number of operators.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Adaptation {
+    /// Keep the weights of the operators
+    Fixed,
+    /// Reward 1 for an accepted move and 0 otherwise
+    SuccessRate {
+        learning_rate: f64,
+        min_probability: f64,
+    },
+    /// Reward the decrease of the cost by an accepted move relative to the largest decrease so far,
+    /// 0 when it did not decrease, so that the rewards do not depend on the unit of the energy
+    Improvement {
+        learning_rate: f64,
+        min_probability: f64,
+    },
+}
+
+/// Move generator choosing among operators by probability, replacing a hand written `Transition::choose`.
+/// Set on the annealer with `Annealer::with_moves`.
+/// Operators are functions or move generators of their own, which get the feedback on the moves they chose.
+/// The operators are not serialized, a deserialized checkpoint restores the adaptive state and statistics
+/// into the operators of the resuming annealer.
+#[derive(Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(bound = "")
+)]
+pub struct CompositeMoves<S: AnnealingState> {
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub operators: Vec<Operator<S>>,
+    pub adaptation: Adaptation,
+    /// Statistics of each operator, accumulated over runs
+    pub stats: Vec<OperatorStats>,
+    qualities: Vec<f64>,
+    probabilities: Vec<f64>,
+    /// Largest decrease of the cost so far, which normalizes the rewards of `Adaptation::Improvement`
+    max_gain: f64,
+    /// Operator of the last chosen move
+    last: Option<usize>,
+}
+
+impl<S: AnnealingState> CompositeMoves<S> {
+    pub fn new(adaptation: Adaptation) -> Self {
+        Self {
+            operators: Vec::new(),
+            adaptation,
+            stats: Vec::new(),
+            qualities: Vec::new(),
+            probabilities: Vec::new(),
+            max_gain: 0.0,
+            last: None,
+        }
+    }
+
+    /// Add an operator generating its moves with a function, chosen with a probability proportional to `weight`.
+    pub fn with_operator<F>(self, name: &'static str, weight: f64, generate: F) -> Self
+    where
+        F: FnMut(&mut dyn RngCore, &S::Context, &S) -> S::Transition + Clone + 'static,
+    {
+        self.with_generator(name, weight, FnMoves(generate))
+    }
+
+    /// Add an operator generating its moves with a move generator, chosen with a probability proportional to `weight`.
+    /// Some weight must be positive when a move is chosen.
+    pub fn with_generator<N>(mut self, name: &'static str, weight: f64, generator: N) -> Self
+    where
+        N: MoveGenerator<S> + Clone + 'static,
+    {
+        assert!(weight >= 0.0, "weight must not be negative");
+        self.operators.push(Operator {
+            name,
+            weight,
+            generator: Box::new(generator),
+        });
+        self.stats.push(OperatorStats::default());
+        let total = self.total_weight();
+        self.qualities = self
+            .operators
+            .iter()
+            .map(|operator| {
+                if total > 0.0 {
+                    operator.weight / total
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        self.probabilities = self.qualities.clone();
+        self
+    }
+
+    /// Current probability of choosing each operator.
+    pub fn probabilities(&self) -> &[f64] {
+        &self.probabilities
+    }
+
+    fn total_weight(&self) -> f64 {
+        self.operators.iter().map(|operator| operator.weight).sum()
+    }
+
+    fn choose_operator<G: Rng>(&self, rng: &mut G) -> usize {
+        assert!(!self.operators.is_empty(), "no operator");
+        assert!(
+            self.total_weight() > 0.0,
+            "weights of the operators must not all be zero"
+        );
+        let mut p = rng.gen_range(0.0..1.0);
+        self.probabilities
+            .iter()
+            .position(|&probability| {
+                p -= probability;
+                p < 0.0
+            })
+            .unwrap_or(self.operators.len() - 1)
+    }
+
+    /// Record the outcome of the move of the operator and adapt the probabilities.
+    fn record(&mut self, operator: usize, outcome: MoveOutcome) {
+        let stats = &mut self.stats[operator];
+        stats.attempts += 1;
+        let reward = match outcome {
+            MoveOutcome::Invalid => {
+                stats.invalid += 1;
+                (0.0, 0.0)
+            }
+            MoveOutcome::Rejected => (0.0, 0.0),
+            MoveOutcome::Accepted { gain, new_best } => {
+                stats.accepts += 1;
+                stats.improvements += (gain > 0.0) as usize;
+                stats.new_bests += new_best as usize;
+                self.max_gain = self.max_gain.max(gain);
+                let improvement = if gain > 0.0 {
+                    gain / self.max_gain
+                } else {
+                    0.0
+                };
+                (1.0, improvement)
+            }
+        };
+
+        let (learning_rate, min_probability, reward) = match self.adaptation {
+            Adaptation::Fixed => return,
+            Adaptation::SuccessRate {
+                learning_rate,
+                min_probability,
+            } => (learning_rate, min_probability, reward.0),
+            Adaptation::Improvement {
+                learning_rate,
+                min_probability,
+            } => (learning_rate, min_probability, reward.1),
+        };
+        let quality = &mut self.qualities[operator];
+        *quality += learning_rate * (reward - *quality);
+
+        let total: f64 = self.qualities.iter().sum();
+        if total <= 0.0 {
+            return;
+        }
+        let operators = self.operators.len() as f64;
+        let min_probability = min_probability.clamp(0.0, 1.0 / operators);
+        for (probability, quality) in self.probabilities.iter_mut().zip(&self.qualities) {
+            *probability = min_probability + (1.0 - operators * min_probability) * quality / total;
+        }
+    }
+}
+
+impl<S: AnnealingState> MoveGenerator<S> for CompositeMoves<S> {
+    fn choose<G: Rng>(&mut self, rng: &mut G, ctx: &S::Context, state: &S) -> S::Transition {
+        let operator = self.choose_operator(rng);
+        self.last = Some(operator);
+        self.operators[operator]
+            .generator
+            .choose_dyn(rng, ctx, state)
+    }
+
+    fn feedback(&mut self, op: &S::Transition, outcome: MoveOutcome) {
+        if let Some(operator) = self.last.take() {
+            self.operators[operator].generator.feedback_dyn(op, outcome);
+            self.record(operator, outcome);
+        }
+    }
+
+    /// Take the saved generator, keeping the operators when they were not saved because it was deserialized.
+    fn restore(&mut self, saved: Self) {
+        assert_eq!(
+            saved.stats.len(),
+            self.operators.len(),
+            "saved moves have a different number of operators"
+        );
+        if saved.operators.is_empty() {
+            *self = Self {
+                operators: std::mem::take(&mut self.operators),
+                ..saved
+            };
+        } else {
+            *self = saved;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::acceptance::Metropolis;
+    use crate::run::CloneMode;
+    use crate::schedule::LinearStepSchedule;
+    use crate::tests::{
+        quadratic_annealer, QuadraticFunction, QuadraticFunctionState, QuadraticFunctionTransition,
+    };
+    use crate::Annealer;
+
+    fn moves(adaptation: Adaptation) -> CompositeMoves<QuadraticFunctionState> {
+        CompositeMoves::new(adaptation)
+            .with_operator("add", 3.0, |rng, _, _| {
+                QuadraticFunctionTransition::Add(rng.gen_range(-10.0..=10.0))
+            })
+            .with_operator("mul", 1.0, |rng, _, _| {
+                QuadraticFunctionTransition::Mul(rng.gen_range(0.3..=1.1))
+            })
+    }
+
+    #[test]
+    fn weights() {
+        let mut moves = moves(Adaptation::Fixed);
+        assert_eq!(moves.probabilities(), [0.75, 0.25]);
+
+        let mut rng = SmallRng::seed_from_u64(0);
+        let ctx = QuadraticFunction {
+            a: 1.0,
+            b: 10.0,
+            c: 30.0,
+        };
+        let state = QuadraticFunctionState { x: 0.0 };
+        let adds = (0..10000)
+            .filter(|_| {
+                let op = moves.choose(&mut rng, &ctx, &state);
+                matches!(op, QuadraticFunctionTransition::Add(_))
+            })
+            .count();
+        assert!((adds as f64 / 10000.0 - 0.75).abs() < 0.02, "{}", adds);
+
+        assert_eq!(moves.stats[1].acceptance_rate(), 0.0);
+        moves.record(1, MoveOutcome::Rejected);
+        assert_eq!(moves.probabilities(), [0.75, 0.25]);
+    }
+
+    #[test]
+    #[should_panic(expected = "must not all be zero")]
+    fn zero_weights() {
+        let moves = CompositeMoves::new(Adaptation::Fixed)
+            .with_operator("add", 0.0, |rng, _, _| {
+                QuadraticFunctionTransition::Add(rng.gen_range(-10.0..=10.0))
+            })
+            .with_operator("mul", 0.0, |rng, _, _| {
+                QuadraticFunctionTransition::Mul(rng.gen_range(0.3..=1.1))
+            });
+        assert_eq!(moves.probabilities(), [0.0, 0.0]);
+        quadratic_annealer(LinearStepSchedule::new(1000.0, 0.01, 10))
+            .with_moves(moves)
+            .anneal::<_, false>(&mut SmallRng::seed_from_u64(0));
+    }
+
+    #[test]
+    fn success_rate_adapts_probabilities() {
+        let mut moves = moves(Adaptation::SuccessRate {
+            learning_rate: 0.1,
+            min_probability: 0.05,
+        });
+        for _ in 0..100 {
+            moves.record(0, MoveOutcome::Invalid);
+            moves.record(
+                1,
+                MoveOutcome::Accepted {
+                    gain: -1.0,
+                    new_best: false,
+                },
+            );
+        }
+
+        let probabilities = moves.probabilities();
+        assert!(
+            (probabilities[0] - 0.05).abs() < 1e-3,
+            "{:?}",
+            probabilities
+        );
+        assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert_eq!(moves.stats[0].invalid, 100);
+        assert_eq!(moves.stats[1].acceptance_rate(), 1.0);
+        assert_eq!(moves.stats[1].improvements, 0);
+    }
+
+    #[test]
+    fn improvement_does_not_depend_on_the_unit_of_the_energy() {
+        let adapted = |scale: f64| {
+            let mut moves = moves(Adaptation::Improvement {
+                learning_rate: 0.1,
+                min_probability: 0.05,
+            });
+            for gain in [2.0, -1.0, 0.5, 3.0] {
+                let outcome = MoveOutcome::Accepted {
+                    gain: gain * scale,
+                    new_best: false,
+                };
+                moves.record(0, outcome);
+                moves.record(1, MoveOutcome::Rejected);
+            }
+            moves.probabilities().to_vec()
+        };
+
+        let probabilities = adapted(1.0);
+        assert!(probabilities[0] > 0.75, "{:?}", probabilities);
+        for (a, b) in probabilities.iter().zip(adapted(1e6)) {
+            assert!((a - b).abs() < 1e-12);
+        }
+    }
+
+    /// Steps of a size which grows on acceptance and shrinks otherwise.
+    /// The feedbacks are counted in a cell shared with the test, as the operator is boxed.
+    #[derive(Clone)]
+    struct AdaptiveAdd {
+        size: f64,
+        feedbacks: Rc<Cell<usize>>,
+    }
+
+    impl MoveGenerator<QuadraticFunctionState> for AdaptiveAdd {
+        fn choose<G: Rng>(
+            &mut self,
+            rng: &mut G,
+            _ctx: &QuadraticFunction,
+            _state: &QuadraticFunctionState,
+        ) -> QuadraticFunctionTransition {
+            QuadraticFunctionTransition::Add(rng.gen_range(-self.size..=self.size))
+        }
+
+        fn feedback(&mut self, op: &QuadraticFunctionTransition, outcome: MoveOutcome) {
+            assert!(matches!(op, QuadraticFunctionTransition::Add(_)));
+            self.feedbacks.set(self.feedbacks.get() + 1);
+            match outcome {
+                MoveOutcome::Accepted { .. } => self.size *= 1.1,
+                _ => self.size = (self.size * 0.95).max(1e-3),
+            }
+        }
+    }
+
+    #[test]
+    fn feedback_reaches_the_generator_of_the_operator() {
+        let feedbacks = Rc::new(Cell::new(0));
+        let moves = CompositeMoves::new(Adaptation::Fixed)
+            .with_generator(
+                "add",
+                1.0,
+                AdaptiveAdd {
+                    size: 10.0,
+                    feedbacks: feedbacks.clone(),
+                },
+            )
+            .with_operator("mul", 1.0, |rng, _, _| {
+                QuadraticFunctionTransition::Mul(rng.gen_range(0.3..=1.1))
+            });
+        let mut annealer =
+            quadratic_annealer(LinearStepSchedule::new(100.0, 0.01, 5000)).with_moves(moves);
+        let result = annealer.anneal::<_, false>(&mut SmallRng::seed_from_u64(0));
+
+        assert!((result.best_state.x - (-5.0)).abs() < 0.1);
+        assert!(feedbacks.get() > 0);
+        assert_eq!(feedbacks.get(), annealer.moves.stats[0].attempts);
+    }
+
+    #[test]
+    fn anneal_with_composite_moves() {
+        let mut annealer = quadratic_annealer(LinearStepSchedule::new(1000.0, 0.01, 10000))
+            .with_moves(moves(Adaptation::Improvement {
+                learning_rate: 0.05,
+                min_probability: 0.1,
+            }));
+        let result = annealer.anneal::<_, false>(&mut SmallRng::seed_from_u64(0));
+
+        assert!((result.best_state.x - (-5.0)).abs() < 0.1);
+        let moves = &annealer.moves;
+        let stats = &moves.stats;
+        assert_eq!(stats[0].attempts + stats[1].attempts, result.steps);
+        assert_eq!(stats[0].accepts + stats[1].accepts, result.accepted);
+        assert!(stats.iter().all(|stats| stats.new_bests > 0));
+        assert!(moves.probabilities().iter().all(|&p| p >= 0.1 - 1e-9));
+    }
+
+    fn adaptive_annealer() -> Annealer<
+        QuadraticFunctionState,
+        LinearStepSchedule,
+        Metropolis,
+        CompositeMoves<QuadraticFunctionState>,
+    > {
+        quadratic_annealer(LinearStepSchedule::new(1000.0, 0.01, 2000)).with_moves(moves(
+            Adaptation::SuccessRate {
+                learning_rate: 0.1,
+                min_probability: 0.01,
+            },
+        ))
+    }
+
+    #[test]
+    fn resume_with_adaptive_moves() {
+        let mut uninterrupted = adaptive_annealer();
+        uninterrupted.anneal::<_, true>(&mut SmallRng::seed_from_u64(0));
+
+        let mut interrupted = adaptive_annealer();
+        let mut run = interrupted.start::<_, true>(SmallRng::seed_from_u64(0));
+        run.run_for(1000);
+        let checkpoint = run.checkpoint();
+        assert_ne!(checkpoint.moves.probabilities(), [0.75, 0.25]);
+
+        let mut resumed = adaptive_annealer();
+        resumed.resume::<CloneMode, _, true>(checkpoint).finish();
+
+        let energies = |annealer: &Annealer<_, _, _, _>| -> Vec<f64> {
+            annealer.metrics.iter().map(|m| m.current_energy).collect()
+        };
+        assert_eq!(energies(&resumed), energies(&uninterrupted)[1000..]);
+        assert_eq!(
+            resumed.moves.probabilities(),
+            uninterrupted.moves.probabilities()
+        );
+        assert_eq!(
+            resumed.moves.stats[0].attempts,
+            uninterrupted.moves.stats[0].attempts
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn resume_adaptive_moves_from_json() {
+        use rand_chacha::ChaCha8Rng;
+
+        use crate::checkpoint::Checkpoint;
+
+        let mut uninterrupted = adaptive_annealer();
+        let expected = uninterrupted
+            .anneal::<_, false>(&mut ChaCha8Rng::seed_from_u64(0))
+            .best_state;
+
+        let mut interrupted = adaptive_annealer();
+        let mut run = interrupted.start::<_, false>(ChaCha8Rng::seed_from_u64(0));
+        run.run_for(1000);
+        let json = serde_json::to_string(&run.checkpoint()).unwrap();
+
+        let checkpoint: Checkpoint<
+            _,
+            LinearStepSchedule,
+            Metropolis,
+            ChaCha8Rng,
+            CompositeMoves<QuadraticFunctionState>,
+        > = serde_json::from_str(&json).unwrap();
+        let mut resumed = adaptive_annealer();
+        let best_state: QuadraticFunctionState = resumed
+            .resume::<CloneMode, _, false>(checkpoint)
+            .finish()
+            .best_state;
+
+        assert_eq!(best_state.x, expected.x);
+        assert_eq!(
+            resumed.moves.probabilities(),
+            uninterrupted.moves.probabilities()
+        );
+    }
+}
@@ -0,0 +1,370 @@
+use std::fmt::Debug;
+
+/// Energy is a value which can be compared exactly and converted to f64
+/// for the acceptance criteria, metrics and statistics.
+/// Implemented for all primitive integers and floats, and for the `ordered-float`
+/// types with the `ordered-float` feature.
+pub trait Energy: PartialOrd + Copy + Debug {
+    fn to_f64(self) -> f64;
+
+    /// `self - from` as f64, without overflowing for integers.
+    fn delta(self, from: Self) -> f64;
+}
+
+/// Whether the annealer minimizes or maximizes the energy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    #[default]
+    Minimize,
+    Maximize,
+}
+
+impl Direction {
+    /// Whether `energy` is strictly better than `than`.
+    pub fn is_better<E: Energy>(self, energy: E, than: E) -> bool {
+        match self {
+            Direction::Minimize => energy < than,
+            Direction::Maximize => energy > than,
+        }
+    }
+
+    /// Change of the cost to minimize from `from` to `energy`, which is what the acceptance criteria compare.
+    /// The cost is the energy when minimizing and its negation when maximizing.
+    pub fn cost_delta<E: Energy>(self, energy: E, from: E) -> f64 {
+        match self {
+            Direction::Minimize => energy.delta(from),
+            Direction::Maximize => -energy.delta(from),
+        }
+    }
+}
+
+macro_rules! impl_energy_float {
+    ($($t:ty),*) => {$(
+        impl Energy for $t {
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn delta(self, from: Self) -> f64 {
+                self as f64 - from as f64
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_energy_signed {
+    ($($t:ty),*) => {$(
+        impl Energy for $t {
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn delta(self, from: Self) -> f64 {
+                match self.checked_sub(from) {
+                    Some(delta) => delta as f64,
+                    None => self as f64 - from as f64,
+                }
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_energy_unsigned {
+    ($($t:ty),*) => {$(
+        impl Energy for $t {
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+
+            fn delta(self, from: Self) -> f64 {
+                if self >= from {
+                    (self - from) as f64
+                } else {
+                    -((from - self) as f64)
+                }
+            }
+        }
+    )*};
+}
+
+impl_energy_float!(f32, f64);
+impl_energy_signed!(i8, i16, i32, i64, i128, isize);
+impl_energy_unsigned!(u8, u16, u32, u64, u128, usize);
+
+#[cfg(feature = "ordered-float")]
+macro_rules! impl_energy_ordered_float {
+    ($($t:ty),*) => {$(
+        impl Energy for $t {
+            fn to_f64(self) -> f64 {
+                self.into_inner() as f64
+            }
+
+            fn delta(self, from: Self) -> f64 {
+                self.to_f64() - from.to_f64()
+            }
+        }
+    )*};
+}
+
+#[cfg(feature = "ordered-float")]
+impl_energy_ordered_float!(
+    ordered_float::OrderedFloat<f32>,
+    ordered_float::OrderedFloat<f64>,
+    ordered_float::NotNan<f32>,
+    ordered_float::NotNan<f64>
+);
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::metrics::Metrics;
+    use crate::population::PopulationAnnealer;
+    use crate::schedule::ExponentialStepSchedule;
+    use crate::tempering::ParallelTempering;
+    use crate::{
+        Annealer, AnnealingState, AnnealingStateBack, AnnealingStatePeeking, EnergyMeasurable,
+        Transition,
+    };
+
+    /// Distance to the target on the integers, scaled beyond the range of i32.
+    /// When maximizing the energy is u64::MAX minus the distance.
+    #[derive(Debug, Clone)]
+    struct IntegerState {
+        x: i64,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    struct Move(i64);
+
+    const TARGET: i64 = 3_000_000_000;
+    const SCALE: u64 = 1_000_000_000;
+
+    impl Transition for Move {
+        type Context = Direction;
+        type State = IntegerState;
+
+        fn choose<G: Rng>(rng: &mut G, _ctx: &Self::Context, _state: &Self::State) -> Self {
+            Move(rng.gen_range(-10..=10))
+        }
+    }
+
+    impl EnergyMeasurable for IntegerState {
+        type Energy = u64;
+        type Context = Direction;
+
+        fn energy(&self, ctx: &Self::Context) -> Self::Energy {
+            let distance = (self.x - TARGET).unsigned_abs() * SCALE;
+            match ctx {
+                Direction::Minimize => distance,
+                Direction::Maximize => u64::MAX - distance,
+            }
+        }
+    }
+
+    impl AnnealingState for IntegerState {
+        type Transition = Move;
+
+        fn apply(&mut self, _ctx: &Self::Context, op: &Self::Transition) -> Option<()> {
+            self.x += op.0;
+            Some(())
+        }
+    }
+
+    impl AnnealingStatePeeking for IntegerState {
+        fn peek_energy(
+            &self,
+            ctx: &Self::Context,
+            op: &Self::Transition,
+            _current_energy: Self::Energy,
+        ) -> Option<Self::Energy> {
+            Some(IntegerState { x: self.x + op.0 }.energy(ctx))
+        }
+    }
+
+    impl AnnealingStateBack for IntegerState {
+        type Restore = i64;
+
+        fn apply_with_restore(
+            &mut self,
+            _ctx: &Self::Context,
+            op: &Self::Transition,
+        ) -> Option<Self::Restore> {
+            let prev_x = self.x;
+            self.x += op.0;
+            Some(prev_x)
+        }
+
+        fn back(&mut self, _ctx: &Self::Context, restore: &Self::Restore) {
+            self.x = *restore;
+        }
+    }
+
+    #[test]
+    fn solve_with_large_integer_energy() {
+        for direction in [Direction::Minimize, Direction::Maximize] {
+            let annealer = || {
+                Annealer::new(
+                    IntegerState { x: TARGET + 1000 },
+                    direction,
+                    ExponentialStepSchedule::new(1e11, 1e8, 20000),
+                )
+                .with_direction(direction)
+            };
+            let rng = || SmallRng::seed_from_u64(0);
+            let optimum = IntegerState { x: TARGET }.energy(&direction);
+
+            let results = [
+                annealer().anneal::<_, true>(&mut rng()),
+                annealer().anneal_back::<_, true>(&mut rng()),
+                annealer().anneal_peek::<_, true>(&mut rng()),
+                annealer().anneal_peek_back::<_, true>(&mut rng()),
+            ];
+            for result in results {
+                assert_eq!(result.best_state.x, TARGET);
+                assert_eq!(result.best_energy, optimum);
+            }
+        }
+    }
+
+    /// Distance to 0 near u64::MAX, where f64 can not tell the energies of neighbours apart.
+    #[derive(Debug, Clone)]
+    struct NearMaxState {
+        x: i64,
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    struct Step(i64);
+
+    impl Transition for Step {
+        type Context = Direction;
+        type State = NearMaxState;
+
+        fn choose<G: Rng>(rng: &mut G, _ctx: &Self::Context, _state: &Self::State) -> Self {
+            Step(if rng.gen() { 1 } else { -1 })
+        }
+    }
+
+    impl EnergyMeasurable for NearMaxState {
+        type Energy = u64;
+        type Context = Direction;
+
+        fn energy(&self, ctx: &Self::Context) -> Self::Energy {
+            match ctx {
+                Direction::Minimize => u64::MAX - 10_000 + self.x.unsigned_abs(),
+                Direction::Maximize => u64::MAX - self.x.unsigned_abs(),
+            }
+        }
+    }
+
+    impl AnnealingState for NearMaxState {
+        type Transition = Step;
+
+        fn apply(&mut self, _ctx: &Self::Context, op: &Self::Transition) -> Option<()> {
+            self.x += op.0;
+            Some(())
+        }
+    }
+
+    #[test]
+    fn unit_deltas_near_u64_max() {
+        for direction in [Direction::Minimize, Direction::Maximize] {
+            let mut annealer = Annealer::new(
+                NearMaxState { x: 500 },
+                direction,
+                ExponentialStepSchedule::new(1e-6, 1e-7, 3000),
+            )
+            .with_direction(direction);
+            let result = annealer.anneal::<_, true>(&mut SmallRng::seed_from_u64(0));
+
+            assert_eq!(result.best_state.x, 0);
+            assert_eq!(result.best_energy, NearMaxState { x: 0 }.energy(&direction));
+            let worse = |m: &&Metrics| match direction {
+                Direction::Minimize => m.delta > 0.0,
+                Direction::Maximize => m.delta < 0.0,
+            };
+            assert_eq!(
+                annealer
+                    .metrics
+                    .iter()
+                    .filter(|m| m.accept)
+                    .filter(worse)
+                    .count(),
+                0
+            );
+        }
+    }
+
+    #[test]
+    fn unit_deltas_near_u64_max_tempering_and_population() {
+        let optimum = NearMaxState { x: 0 }.energy(&Direction::Minimize);
+
+        let mut tempering = ParallelTempering::new(
+            NearMaxState { x: 500 },
+            Direction::Minimize,
+            vec![1e-7, 1e-6],
+            100,
+        );
+        let state = tempering.run(&mut SmallRng::seed_from_u64(0), 30);
+        assert_eq!(state.x, 0);
+
+        let mut population = PopulationAnnealer::new(
+            NearMaxState { x: 500 },
+            Direction::Minimize,
+            ExponentialStepSchedule::new(1e-6, 1e-7, 30),
+            4,
+            100,
+        );
+        let state = population.run(&mut SmallRng::seed_from_u64(0));
+        assert_eq!(state.x, 0);
+        assert_eq!(
+            population.steps.last().unwrap().best_energy,
+            optimum.to_f64()
+        );
+    }
+
+    #[test]
+    fn maximize_metrics() {
+        let mut annealer = Annealer::new(
+            IntegerState { x: TARGET + 1000 },
+            Direction::Maximize,
+            ExponentialStepSchedule::new(1e11, 1e8, 5000),
+        )
+        .with_direction(Direction::Maximize);
+        let result = annealer.anneal::<_, true>(&mut SmallRng::seed_from_u64(1));
+
+        let metrics = &annealer.metrics;
+        assert!(metrics
+            .windows(2)
+            .all(|pair| pair[0].best_energy <= pair[1].best_energy));
+        assert_eq!(
+            metrics.last().unwrap().best_energy,
+            result.best_energy.to_f64()
+        );
+        for m in metrics.iter().filter(|m| m.improvement) {
+            assert!(m.delta > 0.0);
+        }
+    }
+
+    #[test]
+    fn delta_does_not_overflow() {
+        assert_eq!(3u64.delta(5), -2.0);
+        assert_eq!(u64::MAX.delta(0), u64::MAX as f64);
+        assert_eq!(0u64.delta(u64::MAX), -(u64::MAX as f64));
+        assert_eq!(i64::MAX.delta(i64::MIN), i64::MAX as f64 * 2.0);
+        assert_eq!((-3i32).delta(4), -7.0);
+        assert_eq!(1.5f32.delta(0.5), 1.0);
+    }
+
+    #[cfg(feature = "ordered-float")]
+    #[test]
+    fn ordered_float() {
+        use ordered_float::{NotNan, OrderedFloat};
+
+        assert_eq!(OrderedFloat(2.5f64).delta(OrderedFloat(1.0)), 1.5);
+        assert_eq!(NotNan::new(2.0f32).unwrap().to_f64(), 2.0);
+    }
+}
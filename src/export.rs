@@ -0,0 +1,211 @@
+use std::io::{self, Write};
+
+use log::error;
+
+use crate::metrics::Metrics;
+use crate::observer::Observer;
+use crate::EnergyMeasurable;
+
+const CSV_HEADER: &str = "step,best_energy,current_energy,next_energy,delta,accept,improvement,restart,progress,temperature,step_duration";
+
+fn write_csv_row<W: Write>(writer: &mut W, metrics: &Metrics) -> io::Result<()> {
+    writeln!(
+        writer,
+        "{},{},{},{},{},{},{},{},{},{},{}",
+        metrics.step,
+        metrics.best_energy,
+        metrics.current_energy,
+        metrics.next_energy,
+        metrics.delta,
+        metrics.accept,
+        metrics.improvement,
+        metrics.restart,
+        metrics.progress,
+        metrics.temperature,
+        metrics.step_duration.as_secs_f64()
+    )
+}
+
+#[cfg(feature = "serde")]
+fn write_json_line<W: Write>(writer: &mut W, metrics: &Metrics) -> io::Result<()> {
+    serde_json::to_writer(&mut *writer, metrics)?;
+    writeln!(writer)
+}
+
+/// Write metrics as CSV with a header line. `step_duration` is written in seconds.
+pub fn write_csv<W: Write>(mut writer: W, metrics: &[Metrics]) -> io::Result<()> {
+    writeln!(writer, "{}", CSV_HEADER)?;
+    for metrics in metrics {
+        write_csv_row(&mut writer, metrics)?;
+    }
+    writer.flush()
+}
+
+/// Write metrics as JSON Lines, one object per step. `step_duration` is written in seconds.
+#[cfg(feature = "serde")]
+pub fn write_json_lines<W: Write>(mut writer: W, metrics: &[Metrics]) -> io::Result<()> {
+    for metrics in metrics {
+        write_json_line(&mut writer, metrics)?;
+    }
+    writer.flush()
+}
+
+/// Write with the writer until the first error, which is logged.
+fn stream<W: Write>(writer: &mut Option<W>, f: impl FnOnce(&mut W) -> io::Result<()>) {
+    if let Some(w) = writer.as_mut() {
+        if let Err(e) = f(w) {
+            error!("failed to write metrics: {}", e);
+            *writer = None;
+        }
+    }
+}
+
+/// Observer streaming the metrics of each step as CSV while annealing with `METRICS = true`.
+/// Writing stops at the first IO error, which is logged.
+pub struct CsvMetricsWriter<W: Write> {
+    writer: Option<W>,
+    header: bool,
+}
+
+impl<W: Write> CsvMetricsWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Some(writer),
+            header: false,
+        }
+    }
+}
+
+impl<S: EnergyMeasurable, W: Write> Observer<S> for CsvMetricsWriter<W> {
+    fn on_metrics(&mut self, metrics: &Metrics) {
+        let header = std::mem::replace(&mut self.header, true);
+        stream(&mut self.writer, |writer| {
+            if !header {
+                writeln!(writer, "{}", CSV_HEADER)?;
+            }
+            write_csv_row(writer, metrics)
+        });
+    }
+
+    fn on_finish(&mut self, _best_state: &S, _best_energy: S::Energy) {
+        stream(&mut self.writer, |writer| writer.flush());
+    }
+}
+
+/// Observer streaming the metrics of each step as JSON Lines while annealing with `METRICS = true`.
+/// Writing stops at the first IO error, which is logged.
+#[cfg(feature = "serde")]
+pub struct JsonLinesMetricsWriter<W: Write> {
+    writer: Option<W>,
+}
+
+#[cfg(feature = "serde")]
+impl<W: Write> JsonLinesMetricsWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Some(writer),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<S: EnergyMeasurable, W: Write> Observer<S> for JsonLinesMetricsWriter<W> {
+    fn on_metrics(&mut self, metrics: &Metrics) {
+        stream(&mut self.writer, |writer| write_json_line(writer, metrics));
+    }
+
+    fn on_finish(&mut self, _best_state: &S, _best_energy: S::Energy) {
+        stream(&mut self.writer, |writer| writer.flush());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::metrics::MetricsConfig;
+    use crate::schedule::LinearStepSchedule;
+    use crate::tests::{QuadraticFunction, QuadraticFunctionState};
+    use crate::Annealer;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn metrics(step: usize) -> Metrics {
+        Metrics {
+            step,
+            best_energy: 1.0,
+            current_energy: 2.0,
+            next_energy: 3.5,
+            delta: 1.5,
+            accept: false,
+            improvement: false,
+            restart: false,
+            progress: 0.25,
+            temperature: 10.0,
+            step_duration: Duration::from_millis(500),
+        }
+    }
+
+    #[test]
+    fn csv() {
+        let mut buffer = Vec::new();
+        write_csv(&mut buffer, &[metrics(1), metrics(2)]).unwrap();
+
+        let csv = String::from_utf8(buffer).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[2], "2,1,2,3.5,1.5,false,false,false,0.25,10,0.5");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_lines() {
+        let mut buffer = Vec::new();
+        write_json_lines(&mut buffer, &[metrics(1), metrics(2)]).unwrap();
+
+        let json = String::from_utf8(buffer).unwrap();
+        let records: Vec<Metrics> = json
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].step, 2);
+        assert_eq!(records[1].step_duration, Duration::from_millis(500));
+    }
+
+    #[test]
+    fn stream_csv_while_annealing() {
+        let buffer = SharedBuffer::default();
+        let mut annealer = Annealer::new(
+            QuadraticFunctionState { x: 100.0 },
+            QuadraticFunction {
+                a: 1.0,
+                b: 10.0,
+                c: 30.0,
+            },
+            LinearStepSchedule::new(1000.0, 0.01, 1000),
+        )
+        .with_metrics_config(MetricsConfig::Last(1))
+        .with_observer(CsvMetricsWriter::new(buffer.clone()));
+
+        annealer.anneal::<_, true>(&mut rand::thread_rng());
+
+        assert_eq!(annealer.metrics.len(), 1);
+        let csv = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(csv.lines().count(), 1001);
+    }
+}
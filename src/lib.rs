@@ -1,17 +1,37 @@
 use std::fmt::Debug;
-use std::ops::{Neg, Sub};
 
 use log::debug;
-use num_traits::{Num, Signed};
 use rand::Rng;
 
 use schedule::Schedule;
 
-use crate::metrics::Metrics;
+use crate::acceptance::{Acceptance, LateAcceptance, Metropolis};
+use crate::checkpoint::Checkpoint;
+use crate::energy::{Direction, Energy};
+use crate::metrics::{Metrics, MetricsBucket, MetricsConfig};
+use crate::moves::{MoveGenerator, TransitionMoves};
+use crate::observer::Observer;
+use crate::restart::{RestartPolicy, RestartStrategy, Restarts};
+use crate::run::{AnnealResult, AnnealRun, BackMode, CloneMode, PeekBackMode, PeekMode, StepMode};
 use crate::schedule::Progress;
+use crate::stop::{AnnealStatus, StopCondition, StopReason};
 
-mod metrics;
+pub mod acceptance;
+mod auto;
+pub mod checkpoint;
+pub mod composite;
+pub mod energy;
+pub mod export;
+pub mod metrics;
+pub mod moves;
+pub mod multi_start;
+pub mod observer;
+pub mod population;
+pub mod restart;
+pub mod run;
 pub mod schedule;
+pub mod stop;
+pub mod tempering;
 pub mod test_implementer;
 
 /// Transition is a trait to be implemented when the state can be updated by a transition.
@@ -64,7 +84,7 @@ pub mod test_implementer;
 ///     QuadraticFunctionTransition::Add(_) | QuadraticFunctionTransition::Mul(_)
 /// ));
 /// ```
-pub trait Transition: Sized + Clone + Copy {
+pub trait Transition: Sized + Clone {
     type Context;
     type State;
 
@@ -106,17 +126,17 @@ pub trait Transition: Sized + Clone + Copy {
 /// assert_eq!(state.energy(&func), 5.0);
 /// ```
 pub trait EnergyMeasurable: Sized + Clone + Debug {
-    type Energy: PartialOrd + Clone + Copy + Debug + Sub + Signed + Num + Neg + Into<f64>;
+    type Energy: Energy;
     type Context;
 
     fn energy(&self, ctx: &Self::Context) -> Self::Energy;
 }
 
-/// InitialState is a trait to be implemented when the initial state can be generated.
+/// InitialState is a trait to be implemented when a random starting state can be generated from the context.
 /// e.g.
 /// ```rust
 /// use rand::Rng;
-/// use rusty_simanneal::InitialState;
+/// use rusty_simanneal::{EnergyMeasurable, InitialState};
 ///
 /// struct QuadraticFunction {
 ///    a: f64,
@@ -129,19 +149,33 @@ pub trait EnergyMeasurable: Sized + Clone + Debug {
 ///     x: f64,
 /// }
 ///
-/// impl InitialState for QuadraticFunction {
-///     type Context = QuadraticFunctionState;
-///     fn initial_state<G: Rng>(&self, rng: &mut G, _ctx: &Self::Context) -> QuadraticFunctionState {
+/// impl EnergyMeasurable for QuadraticFunctionState {
+///     type Energy = f64;
+///     type Context = QuadraticFunction;
+///
+///     fn energy(&self, ctx: &Self::Context) -> Self::Energy {
+///         ctx.a * self.x * self.x + ctx.b * self.x + ctx.c
+///     }
+/// }
+///
+/// impl InitialState for QuadraticFunctionState {
+///     fn initial_state<G: Rng>(rng: &mut G, _ctx: &Self::Context) -> Self {
 ///         QuadraticFunctionState {
 ///             x: rng.gen_range(-100.0..=100.0),
-///        }
-///    }
+///         }
+///     }
 /// }
+///
+/// let func = QuadraticFunction {
+///     a: 1.0,
+///     b: 10.0,
+///     c: 30.0,
+/// };
+/// let state = QuadraticFunctionState::initial_state(&mut rand::thread_rng(), &func);
+/// assert!((-100.0..=100.0).contains(&state.x));
 /// ```
-pub trait InitialState {
-    type Context;
-
-    fn initial_state<G: Rng>(&self, rng: &mut G, ctx: &Self::Context) -> Self;
+pub trait InitialState: EnergyMeasurable {
+    fn initial_state<G: Rng>(rng: &mut G, ctx: &Self::Context) -> Self;
 }
 
 /// AnnealingState is a trait to be implemented when the state can be updated by a transition.
@@ -214,7 +248,7 @@ pub trait InitialState {
 ///
 /// let mut state = QuadraticFunctionState { x: 100.0 };
 /// let mut annealer = Annealer::new(state, func, schedule::LinearStepSchedule::new(1000.0, 0.01, 10000));
-/// let best_state = annealer.anneal::<_, false>(&mut rand::thread_rng());
+/// let best_state = annealer.anneal::<_, false>(&mut rand::thread_rng()).best_state;
 /// assert!((best_state.x - (-5.0)).abs() < 0.1);
 /// ```
 pub trait AnnealingState: EnergyMeasurable {
@@ -250,12 +284,30 @@ pub trait AnnealingStateBack: AnnealingState {
 }
 
 /// Simulated Annealing algorithm
-/// minimize f(x) where x is a state
-pub struct Annealer<S: EnergyMeasurable, C: Schedule> {
+/// minimize f(x) where x is a state, or maximize it with `Direction::Maximize`
+pub struct Annealer<
+    S: AnnealingState,
+    C: Schedule,
+    A: Acceptance = Metropolis,
+    N: MoveGenerator<S> = TransitionMoves,
+> {
     pub state: S,
     pub ctx: S::Context,
     pub schedule: C,
+    pub acceptance: A,
+    /// Minimize (default) or maximize the energy
+    pub direction: Direction,
+    /// Checked before each step, annealing stops when any of them is met
+    pub stop_conditions: Vec<Box<dyn StopCondition + Send>>,
+    pub observers: Vec<Box<dyn Observer<S> + Send>>,
+    pub metrics_config: MetricsConfig,
     pub metrics: Vec<Metrics>,
+    /// Aggregated metrics when `metrics_config` is `MetricsConfig::PerTemperature`
+    pub metrics_buckets: Vec<MetricsBucket>,
+    pub restart_policy: Option<RestartPolicy<S>>,
+    /// Chooses the moves, `Transition::choose` by default
+    pub moves: N,
+    restarts: Restarts,
 }
 
 impl<S: AnnealingState, C: Schedule> Annealer<S, C> {
@@ -264,171 +316,311 @@ impl<S: AnnealingState, C: Schedule> Annealer<S, C> {
             state,
             ctx,
             schedule,
+            acceptance: Metropolis,
+            direction: Direction::Minimize,
+            stop_conditions: Vec::new(),
+            observers: Vec::new(),
+            metrics_config: MetricsConfig::All,
             metrics: Vec::new(),
+            metrics_buckets: Vec::new(),
+            restart_policy: None,
+            moves: TransitionMoves,
+            restarts: Restarts::default(),
         }
     }
+}
+
+impl<S: AnnealingState + InitialState, C: Schedule> Annealer<S, C> {
+    /// Start from a state generated by `InitialState`.
+    pub fn from_initial<G: Rng>(ctx: S::Context, schedule: C, rng: &mut G) -> Self {
+        let state = S::initial_state(rng, &ctx);
+        Annealer::new(state, ctx, schedule)
+    }
+}
 
-    pub fn anneal<G: Rng, const METRICS: bool>(&mut self, rng: &mut G) -> S {
-        let mut best_state = self.state.clone();
-        let mut best_energy = self.state.energy(&self.ctx);
-        let mut current_energy = best_energy;
-        let mut progress = Progress::zero();
+impl<S: AnnealingState, C: Schedule> Annealer<S, C, LateAcceptance> {
+    /// Late Acceptance Hill Climbing comparing candidates to the energy `history_length` steps ago.
+    /// The schedule only decides when to stop, its temperature is ignored.
+    pub fn late_acceptance(state: S, ctx: S::Context, schedule: C, history_length: usize) -> Self {
+        Annealer::new(state, ctx, schedule).with_acceptance(LateAcceptance::new(history_length))
+    }
+}
 
-        if METRICS {
-            self.metrics.clear();
+impl<S: AnnealingState, C: Schedule, A: Acceptance, N: MoveGenerator<S>> Annealer<S, C, A, N> {
+    /// Replace the acceptance criterion (Metropolis by default).
+    pub fn with_acceptance<B: Acceptance>(self, acceptance: B) -> Annealer<S, C, B, N> {
+        Annealer {
+            state: self.state,
+            ctx: self.ctx,
+            schedule: self.schedule,
+            acceptance,
+            direction: self.direction,
+            stop_conditions: self.stop_conditions,
+            observers: self.observers,
+            metrics_config: self.metrics_config,
+            metrics: self.metrics,
+            metrics_buckets: self.metrics_buckets,
+            restart_policy: self.restart_policy,
+            moves: self.moves,
+            restarts: self.restarts,
         }
+    }
 
-        while self.schedule.should_continue(&progress) {
-            let start = if METRICS {
-                Some(std::time::Instant::now())
-            } else {
-                None
-            };
+    /// Replace the move generator (`Transition::choose` by default).
+    pub fn with_moves<O: MoveGenerator<S>>(self, moves: O) -> Annealer<S, C, A, O> {
+        Annealer {
+            state: self.state,
+            ctx: self.ctx,
+            schedule: self.schedule,
+            acceptance: self.acceptance,
+            direction: self.direction,
+            stop_conditions: self.stop_conditions,
+            observers: self.observers,
+            metrics_config: self.metrics_config,
+            metrics: self.metrics,
+            metrics_buckets: self.metrics_buckets,
+            restart_policy: self.restart_policy,
+            moves,
+            restarts: self.restarts,
+        }
+    }
 
-            let prev_state = self.state.clone();
-            let op = S::Transition::choose(rng, &self.ctx, &self.state);
+    /// Replace the state with a fresh one generated by `InitialState`,
+    /// so that the next annealing starts over from a random state.
+    pub fn restart<G: Rng>(&mut self, rng: &mut G)
+    where
+        S: InitialState,
+    {
+        self.state = S::initial_state(rng, &self.ctx);
+    }
 
-            let (accept, improvement) = if let Some(_restore) = self.state.apply(&self.ctx, &op) {
-                let temperature = self.schedule.temperature(&progress);
-                let new_energy = self.state.energy(&self.ctx);
+    /// Minimize or maximize the energy.
+    pub fn with_direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
 
-                let improvement = if new_energy < best_energy {
-                    best_energy = new_energy;
-                    best_state = self.state.clone();
-                    true
-                } else {
-                    false
-                };
-
-                let delta = (new_energy - current_energy).into();
-                let p = rng.gen_range(0.0..=1.0);
-                if delta.is_sign_positive() && (-delta / temperature).exp() < p {
-                    // reject
-                    debug!("reject {} -> {}", current_energy.into(), new_energy.into());
-                    self.state = prev_state;
-                    (false, improvement)
-                } else {
-                    // accept
-                    debug!("accept {} -> {}", current_energy.into(), new_energy.into());
-                    current_energy = new_energy;
-                    (true, improvement)
-                }
-            } else {
-                (false, false)
-            };
+    /// Choose how metrics are collected when annealing with `METRICS = true`.
+    pub fn with_metrics_config(mut self, config: MetricsConfig) -> Self {
+        self.metrics_config = config;
+        self
+    }
 
-            if METRICS {
-                self.metrics.push(Metrics {
-                    best_energy: best_energy.into(),
-                    current_energy: current_energy.into(),
-                    next_energy: self.state.energy(&self.ctx).into(),
-                    delta: (self.state.energy(&self.ctx) - current_energy).into(),
-                    accept,
-                    improvement,
-                    progress: self.schedule.progress_0_1(&progress),
-                    temperature: self.schedule.temperature(&progress),
-                    step_duration: start.expect("METRICS = true").elapsed(),
-                });
-            }
+    fn clear_metrics(&mut self) {
+        self.metrics.clear();
+        self.metrics_buckets.clear();
+    }
 
-            progress.update();
+    fn record_metrics(&mut self, metrics: Metrics) {
+        for observer in &mut self.observers {
+            observer.on_metrics(&metrics);
         }
+        self.metrics_config.record(
+            &mut self.metrics,
+            &mut self.metrics_buckets,
+            metrics,
+            self.direction,
+        );
+    }
+
+    /// Add a condition to stop earlier than the schedule.
+    /// The condition must be `Send` so that the annealer can run on another thread, e.g. to be cancelled on shutdown.
+    pub fn with_stop_condition(mut self, condition: impl StopCondition + Send + 'static) -> Self {
+        self.stop_conditions.push(Box::new(condition));
+        self
+    }
+
+    /// Restart when annealing stops improving.
+    pub fn with_restart_policy(mut self, policy: RestartPolicy<S>) -> Self {
+        self.restart_policy = Some(policy);
+        self
+    }
+
+    /// Add an observer notified while annealing.
+    /// The observer must be `Send` so that the annealer can run on another thread.
+    pub fn with_observer(mut self, observer: impl Observer<S> + Send + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Temperature of the schedule raised by reheating.
+    fn temperature(&self, progress: &C::Progress) -> f64 {
+        self.schedule.temperature(progress) * self.restarts.boost()
+    }
 
-        best_state
+    /// Whether the restart policy is due after the step.
+    fn restart_due(&mut self, step: usize, last_improvement: usize) -> bool {
+        self.restarts.step();
+        self.restart_policy
+            .as_ref()
+            .is_some_and(|policy| step - last_improvement.max(self.restarts.last) >= policy.after)
+    }
+
+    /// Restart by the policy.
+    fn restart_by_policy<G: Rng>(
+        &mut self,
+        rng: &mut G,
+        progress: &C::Progress,
+        step: usize,
+        best: (&S, S::Energy),
+        current_energy: &mut S::Energy,
+        initial_energy: S::Energy,
+    ) {
+        let Some(policy) = &self.restart_policy else {
+            return;
+        };
+        match policy.strategy {
+            RestartStrategy::Best => {
+                self.state = best.0.clone();
+                *current_energy = best.1;
+                self.acceptance
+                    .init(self.direction.cost_delta(best.1, initial_energy));
+            }
+            RestartStrategy::Reheat(fraction) => {
+                let t_max = self.schedule.temperature(&C::Progress::zero());
+                let temperature = self.schedule.temperature(progress);
+                self.restarts
+                    .reheat(fraction * t_max / temperature, policy.after);
+            }
+            RestartStrategy::Fresh(fresh) => {
+                self.state = fresh(rng, &self.ctx);
+                *current_energy = self.state.energy(&self.ctx);
+                self.acceptance
+                    .init(self.direction.cost_delta(*current_energy, initial_energy));
+            }
+        }
+        self.restarts.last = step;
+        debug!("restart at step {}", step);
+        for observer in &mut self.observers {
+            observer.on_restart(step, &self.state, *current_energy);
+        }
+    }
+
+    fn check_stop(
+        &mut self,
+        step: usize,
+        last_improvement: usize,
+        current_energy: S::Energy,
+        best_energy: S::Energy,
+    ) -> Option<StopReason> {
+        let status = AnnealStatus {
+            step,
+            current_energy: current_energy.to_f64(),
+            best_energy: best_energy.to_f64(),
+            steps_since_improvement: step - last_improvement,
+            direction: self.direction,
+        };
+        self.stop_conditions
+            .iter_mut()
+            .find_map(|condition| condition.check(&status))
+    }
+
+    pub fn anneal<G: Rng, const METRICS: bool>(&mut self, rng: &mut G) -> AnnealResult<S> {
+        self.start::<_, METRICS>(rng).finish()
+    }
+
+    /// Continue a run from its checkpoint with the same kind of moves, e.g. `BackMode` for `anneal_back`.
+    /// Like `start`, it clears the metrics collected so far and records the steps after the checkpoint.
+    pub fn resume<M: StepMode<S>, G: Rng, const METRICS: bool>(
+        &mut self,
+        checkpoint: Checkpoint<S, C, A, G, N>,
+    ) -> AnnealRun<'_, S, C, A, N, G, M, METRICS> {
+        AnnealRun::resume(self, checkpoint)
+    }
+
+    /// Start annealing step by step, e.g. to interleave it with an event loop.
+    pub fn start<G: Rng, const METRICS: bool>(
+        &mut self,
+        rng: G,
+    ) -> AnnealRun<'_, S, C, A, N, G, CloneMode, METRICS> {
+        AnnealRun::new(self, rng)
     }
 }
 
-impl<S: AnnealingStateBack, C: Schedule> Annealer<S, C> {
+impl<S: AnnealingStateBack, C: Schedule, A: Acceptance, N: MoveGenerator<S>> Annealer<S, C, A, N> {
     /// Simulated Annealing algorithm
-    /// minimize f(x) where x is a state
+    /// minimize f(x) where x is a state, or maximize it with `Direction::Maximize`
     /// Use BACK instead of CLONE when you want to abort and return to the state.
-    pub fn anneal_back<G: Rng, const METRICS: bool>(&mut self, rng: &mut G) -> S {
-        let mut best_state = self.state.clone();
-        let mut best_energy = self.state.energy(&self.ctx);
-        let mut current_energy = best_energy;
-        let mut progress = Progress::zero();
-
-        while self.schedule.should_continue(&progress) {
-            let op = Transition::choose(rng, &self.ctx, &self.state);
-            if let Some(restore) = self.state.apply_with_restore(&self.ctx, &op) {
-                let temperature = self.schedule.temperature(&progress);
-                let new_energy = self.state.energy(&self.ctx);
-                let delta = (new_energy - current_energy).into();
-                let p = rng.gen_range(0.0..=1.0);
-                if delta.is_sign_positive() && (-delta / temperature).exp() < p {
-                    self.state.back(&self.ctx, &restore);
-                } else {
-                    current_energy = new_energy;
-                    if current_energy < best_energy {
-                        best_energy = current_energy;
-                        best_state = self.state.clone();
-                    }
-                }
-            }
-            progress.update();
-        }
+    pub fn anneal_back<G: Rng, const METRICS: bool>(&mut self, rng: &mut G) -> AnnealResult<S> {
+        self.start_back::<_, METRICS>(rng).finish()
+    }
 
-        best_state
+    /// Start annealing step by step with `anneal_back` moves.
+    pub fn start_back<G: Rng, const METRICS: bool>(
+        &mut self,
+        rng: G,
+    ) -> AnnealRun<'_, S, C, A, N, G, BackMode, METRICS> {
+        AnnealRun::new(self, rng)
     }
 }
 
-impl<S: AnnealingStatePeeking, C: Schedule> Annealer<S, C> {
+impl<S: AnnealingStatePeeking, C: Schedule, A: Acceptance, N: MoveGenerator<S>>
+    Annealer<S, C, A, N>
+{
     /// Simulated Annealing algorithm
-    /// minimize f(x) where x is a state
+    /// minimize f(x) where x is a state, or maximize it with `Direction::Maximize`
     /// Use peek_energy instead of apply when the energy of the next state can be calculated efficiently without updating the state.
-    pub fn anneal_peek<G: Rng, const METRICS: bool>(&mut self, rng: &mut G) -> S {
-        let mut best_state = self.state.clone();
-        let mut best_energy = self.state.energy(&self.ctx);
-        let mut current_energy = best_energy;
-        let mut progress = Progress::zero();
-
-        while self.schedule.should_continue(&progress) {
-            let op = Transition::choose(rng, &self.ctx, &self.state);
-            if let Some(new_energy) = self.state.peek_energy(&self.ctx, &op, current_energy) {
-                let temperature = self.schedule.temperature(&progress);
-                let delta = (new_energy - current_energy).into();
-                let p = rng.gen_range(0.0..=1.0);
-                if !(delta.is_sign_positive() && (-delta / temperature).exp() < p) {
-                    // accept
-                    self.state.apply(&self.ctx, &op);
-                    // assert_ulps_eq!(
-                    //     new_energy.into(),
-                    //     self.state.energy(&self.ctx).into(),
-                    //     epsilon = 0.01
-                    // );
-                    current_energy = new_energy;
-                    if current_energy < best_energy {
-                        best_energy = current_energy;
-                        best_state = self.state.clone();
-                    }
-                }
-            }
-            progress.update();
-        }
+    pub fn anneal_peek<G: Rng, const METRICS: bool>(&mut self, rng: &mut G) -> AnnealResult<S> {
+        self.start_peek::<_, METRICS>(rng).finish()
+    }
 
-        best_state
+    /// Start annealing step by step with `anneal_peek` moves.
+    pub fn start_peek<G: Rng, const METRICS: bool>(
+        &mut self,
+        rng: G,
+    ) -> AnnealRun<'_, S, C, A, N, G, PeekMode, METRICS> {
+        AnnealRun::new(self, rng)
+    }
+}
+
+impl<
+        S: AnnealingStatePeeking + AnnealingStateBack,
+        C: Schedule,
+        A: Acceptance,
+        N: MoveGenerator<S>,
+    > Annealer<S, C, A, N>
+{
+    /// Simulated Annealing algorithm
+    /// minimize f(x) where x is a state, or maximize it with `Direction::Maximize`
+    /// Decide with peek_energy like `anneal_peek`, and go back with `AnnealingStateBack`
+    /// to recover the best state instead of cloning it on every improvement.
+    pub fn anneal_peek_back<G: Rng, const METRICS: bool>(
+        &mut self,
+        rng: &mut G,
+    ) -> AnnealResult<S> {
+        self.start_peek_back::<_, METRICS>(rng).finish()
+    }
+
+    /// Start annealing step by step with `anneal_peek_back` moves.
+    pub fn start_peek_back<G: Rng, const METRICS: bool>(
+        &mut self,
+        rng: G,
+    ) -> AnnealRun<'_, S, C, A, N, G, PeekBackMode<S>, METRICS> {
+        AnnealRun::new(self, rng)
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
+    use crate::observer::StepInfo;
 
     /// solve f(x) = a x^2 + b x + c
     #[derive(Debug, Clone)]
-    struct QuadraticFunction {
-        a: f64,
-        b: f64,
-        c: f64,
+    pub(crate) struct QuadraticFunction {
+        pub(crate) a: f64,
+        pub(crate) b: f64,
+        pub(crate) c: f64,
     }
 
     #[derive(Debug, Clone)]
-    struct QuadraticFunctionState {
-        x: f64,
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub(crate) struct QuadraticFunctionState {
+        pub(crate) x: f64,
     }
 
     #[derive(Debug, Clone, Copy)]
-    enum QuadraticFunctionTransition {
+    pub(crate) enum QuadraticFunctionTransition {
         Add(f64),
         Mul(f64),
     }
@@ -473,6 +665,14 @@ mod tests {
         }
     }
 
+    impl InitialState for QuadraticFunctionState {
+        fn initial_state<G: Rng>(rng: &mut G, _ctx: &Self::Context) -> Self {
+            QuadraticFunctionState {
+                x: rng.gen_range(-100.0..=100.0),
+            }
+        }
+    }
+
     impl AnnealingStatePeeking for QuadraticFunctionState {
         fn peek_energy(
             &self,
@@ -514,6 +714,21 @@ mod tests {
         }
     }
 
+    /// Annealer solving f(x) = x^2 + 10 x + 30 from x = 100, whose minimum 5 is at x = -5.
+    pub(crate) fn quadratic_annealer<C: Schedule>(
+        schedule: C,
+    ) -> Annealer<QuadraticFunctionState, C> {
+        Annealer::new(
+            QuadraticFunctionState { x: 100.0 },
+            QuadraticFunction {
+                a: 1.0,
+                b: 10.0,
+                c: 30.0,
+            },
+            schedule,
+        )
+    }
+
     #[test]
     fn solve_quadratic_function() {
         let mut annealer = Annealer::new(
@@ -526,7 +741,9 @@ mod tests {
             schedule::LinearStepSchedule::new(1000.0, 0.01, 10000),
         );
 
-        let state = annealer.anneal::<_, false>(&mut rand::thread_rng());
+        let state = annealer
+            .anneal::<_, false>(&mut rand::thread_rng())
+            .best_state;
 
         let QuadraticFunction { a, b, .. } = annealer.ctx;
         let answer = -b / (2.0 * a);
@@ -535,6 +752,28 @@ mod tests {
         assert!((state.x - answer).abs() < 0.1);
     }
 
+    #[test]
+    fn solve_from_initial_state() {
+        let mut rng = rand::thread_rng();
+        let mut annealer: Annealer<QuadraticFunctionState, _> = Annealer::from_initial(
+            QuadraticFunction {
+                a: 1.0,
+                b: 10.0,
+                c: 30.0,
+            },
+            schedule::LinearStepSchedule::new(1000.0, 0.01, 10000),
+            &mut rng,
+        );
+        assert!((-100.0..=100.0).contains(&annealer.state.x));
+        let best_state = annealer.anneal::<_, false>(&mut rng).best_state;
+        assert!((best_state.x - (-5.0)).abs() < 0.1);
+
+        annealer.restart(&mut rng);
+        assert!((-100.0..=100.0).contains(&annealer.state.x));
+        let best_state = annealer.anneal_back::<_, false>(&mut rng).best_state;
+        assert!((best_state.x - (-5.0)).abs() < 0.1);
+    }
+
     #[test]
     fn solve_with_metrics() {
         let mut annealer = Annealer::new(
@@ -547,7 +786,9 @@ mod tests {
             schedule::LinearStepSchedule::new(1000.0, 0.01, 10000),
         );
 
-        let state = annealer.anneal::<_, true>(&mut rand::thread_rng());
+        let state = annealer
+            .anneal::<_, true>(&mut rand::thread_rng())
+            .best_state;
 
         let QuadraticFunction { a, b, .. } = annealer.ctx;
         let answer = -b / (2.0 * a);
@@ -556,4 +797,417 @@ mod tests {
         assert!((state.x - answer).abs() < 0.1);
         assert_ne!(annealer.metrics.len(), 0);
     }
+
+    #[test]
+    fn sampled_and_bounded_metrics() {
+        let new_annealer = |config| {
+            quadratic_annealer(schedule::LinearStepSchedule::new(1000.0, 0.01, 1000))
+                .with_metrics_config(config)
+        };
+
+        let mut annealer = new_annealer(MetricsConfig::Every(10));
+        annealer.anneal::<_, true>(&mut rand::thread_rng());
+        assert_eq!(annealer.metrics.len(), 100);
+        assert_eq!(annealer.metrics[1].step, 11);
+
+        let mut annealer = new_annealer(MetricsConfig::Last(50));
+        annealer.anneal_back::<_, true>(&mut rand::thread_rng());
+        assert_eq!(annealer.metrics.len(), 50);
+        assert_eq!(annealer.metrics[0].step, 951);
+        assert_eq!(annealer.metrics[49].step, 1000);
+
+        let mut annealer = new_annealer(MetricsConfig::PerTemperature(10));
+        annealer.anneal_peek::<_, true>(&mut rand::thread_rng());
+        assert!(annealer.metrics.is_empty());
+        assert_eq!(
+            annealer
+                .metrics_buckets
+                .iter()
+                .map(|bucket| bucket.steps)
+                .sum::<usize>(),
+            1000
+        );
+        let first = annealer.metrics_buckets.first().unwrap();
+        let last = annealer.metrics_buckets.last().unwrap();
+        assert!(first.temperature_min > last.temperature_min);
+        assert!(first.best_energy >= last.best_energy);
+    }
+
+    #[test]
+    fn metrics_in_back_and_peek() {
+        let new_annealer =
+            || quadratic_annealer(schedule::LinearStepSchedule::new(1000.0, 0.01, 1000));
+
+        let mut annealer = new_annealer();
+        annealer.anneal_back::<_, true>(&mut rand::thread_rng());
+        let back_metrics: Vec<metrics::Metrics> = annealer.metrics;
+
+        let mut annealer = new_annealer();
+        annealer.anneal_peek::<_, true>(&mut rand::thread_rng());
+        let peek_metrics = annealer.metrics;
+
+        for metrics in [back_metrics, peek_metrics] {
+            assert_eq!(metrics.len(), 1000);
+            assert!(metrics.iter().any(|m| m.accept));
+            assert!(metrics.iter().any(|m| m.improvement));
+            let mut current_energy = 30.0 + 10.0 * 100.0 + 100.0 * 100.0;
+            for m in &metrics {
+                assert!((m.next_energy - current_energy - m.delta).abs() < 1e-6);
+                if m.accept {
+                    assert_eq!(m.current_energy, m.next_energy);
+                } else {
+                    assert_eq!(m.current_energy, current_energy);
+                }
+                current_energy = m.current_energy;
+            }
+        }
+    }
+
+    #[test]
+    fn stop_on_stagnation() {
+        let mut annealer =
+            quadratic_annealer(schedule::LinearStepSchedule::new(1000.0, 0.01, 100000))
+                .with_stop_condition(stop::Stagnation::new(1000));
+
+        annealer.anneal::<_, true>(&mut rand::thread_rng());
+
+        assert!(annealer.metrics.len() < 100000);
+        let last_improvement = annealer
+            .metrics
+            .iter()
+            .rposition(|metrics| metrics.improvement)
+            .unwrap();
+        assert_eq!(annealer.metrics.len() - last_improvement - 1, 1000);
+    }
+
+    #[test]
+    fn stop_on_target_energy_or_cancellation() {
+        let cancel = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let new_annealer = || {
+            quadratic_annealer(schedule::LinearStepSchedule::new(1000.0, 0.01, 100000))
+                .with_stop_condition(
+                    stop::TargetEnergy::new(5.5).or(stop::Cancellation::new(cancel.clone())),
+                )
+        };
+
+        let state = new_annealer()
+            .anneal_peek::<_, false>(&mut rand::thread_rng())
+            .best_state;
+        assert!(state.energy(&new_annealer().ctx) <= 5.5);
+
+        cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+        let result = new_annealer().anneal_back::<_, false>(&mut rand::thread_rng());
+        assert_eq!(result.best_state.x, 100.0);
+        assert_eq!(result.attempted(), 0);
+        assert_eq!(result.acceptance_rate(), 0.0);
+    }
+
+    #[test]
+    fn annealer_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Annealer<QuadraticFunctionState, schedule::LinearStepSchedule>>();
+    }
+
+    struct CountingObserver {
+        counts: std::sync::Arc<std::sync::Mutex<(usize, usize, usize, usize)>>,
+    }
+
+    impl Observer<QuadraticFunctionState> for CountingObserver {
+        fn on_step(&mut self, _info: &StepInfo) {
+            self.counts.lock().unwrap().0 += 1;
+        }
+
+        fn on_accept(&mut self, _state: &QuadraticFunctionState, _energy: f64) {
+            self.counts.lock().unwrap().1 += 1;
+        }
+
+        fn on_new_best(&mut self, _state: &QuadraticFunctionState, _energy: f64) {
+            self.counts.lock().unwrap().2 += 1;
+        }
+
+        fn on_finish(&mut self, _best_state: &QuadraticFunctionState, _best_energy: f64) {
+            self.counts.lock().unwrap().3 += 1;
+        }
+    }
+
+    #[test]
+    fn observe_every_anneal_mode() {
+        let counts = std::sync::Arc::new(std::sync::Mutex::new((0, 0, 0, 0)));
+        let new_annealer = || {
+            quadratic_annealer(schedule::LinearStepSchedule::new(1000.0, 0.01, 1000))
+                .with_observer(CountingObserver {
+                    counts: counts.clone(),
+                })
+                .with_observer(observer::LogObserver::new(100))
+        };
+
+        new_annealer().anneal::<_, false>(&mut rand::thread_rng());
+        new_annealer().anneal_back::<_, false>(&mut rand::thread_rng());
+        new_annealer().anneal_peek::<_, false>(&mut rand::thread_rng());
+
+        let (steps, accepts, new_bests, finishes) = *counts.lock().unwrap();
+        assert_eq!(steps, 3000);
+        assert!(0 < accepts && accepts <= steps);
+        assert!(0 < new_bests && new_bests <= accepts);
+        assert_eq!(finishes, 3);
+    }
+
+    struct RestartObserver {
+        steps: std::sync::Arc<std::sync::Mutex<Vec<usize>>>,
+    }
+
+    impl Observer<QuadraticFunctionState> for RestartObserver {
+        fn on_restart(&mut self, step: usize, _state: &QuadraticFunctionState, _energy: f64) {
+            self.steps.lock().unwrap().push(step);
+        }
+    }
+
+    #[test]
+    fn restart_policies() {
+        let policies = || {
+            [
+                RestartPolicy::best(200),
+                RestartPolicy::reheat(200, 0.1),
+                RestartPolicy::fresh(200),
+            ]
+        };
+        for (mode, policy) in policies().into_iter().enumerate() {
+            let steps = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            let mut annealer = Annealer::new(
+                QuadraticFunctionState { x: 100.0 },
+                QuadraticFunction {
+                    a: 1.0,
+                    b: 10.0,
+                    c: 30.0,
+                },
+                schedule::LinearStepSchedule::new(1000.0, 0.01, 10000),
+            )
+            .with_restart_policy(policy)
+            .with_observer(RestartObserver {
+                steps: steps.clone(),
+            });
+            let mut rng = rand::thread_rng();
+            let best_state = match mode {
+                0 => annealer.anneal::<_, true>(&mut rng).best_state,
+                1 => annealer.anneal_back::<_, true>(&mut rng).best_state,
+                _ => annealer.anneal_peek::<_, true>(&mut rng).best_state,
+            };
+
+            assert!((best_state.x - (-5.0)).abs() < 0.1);
+            let restarts: Vec<_> = annealer
+                .metrics
+                .iter()
+                .filter(|m| m.restart)
+                .map(|m| m.step)
+                .collect();
+            assert!(!restarts.is_empty());
+            assert_eq!(restarts, *steps.lock().unwrap());
+            assert!(restarts.windows(2).all(|w| w[1] - w[0] >= 200));
+        }
+    }
+
+    #[test]
+    fn reheat_raises_temperature() {
+        let mut annealer = Annealer::new(
+            QuadraticFunctionState { x: -5.0 },
+            QuadraticFunction {
+                a: 1.0,
+                b: 10.0,
+                c: 30.0,
+            },
+            schedule::LinearStepSchedule::new(1000.0, 0.01, 1000),
+        )
+        .with_restart_policy(RestartPolicy::reheat(500, 0.9));
+        annealer.anneal::<_, true>(&mut rand::thread_rng());
+
+        // starting from the minimum, restarts once halfway
+        let restart = annealer.metrics.iter().position(|m| m.restart).unwrap();
+        assert!(restart >= 499);
+        let temperature = annealer.metrics[restart].temperature;
+        assert!(temperature < 600.0);
+        assert!(annealer.metrics[restart + 1].temperature > temperature * 1.5);
+        assert!(
+            annealer.metrics[restart + 100].temperature < annealer.metrics[restart + 1].temperature
+        );
+    }
+
+    #[test]
+    fn solve_with_adaptive_schedule() {
+        let mut annealer = quadratic_annealer(schedule::AdaptiveStepSchedule::new(
+            1000.0, 0.8, 0.01, 10000,
+        ));
+
+        let state = annealer
+            .anneal_back::<_, false>(&mut rand::thread_rng())
+            .best_state;
+
+        assert!((state.x - (-5.0)).abs() < 0.1, "{:?}", state);
+        assert!(
+            annealer.schedule.temperature < 1000.0,
+            "{}",
+            annealer.schedule.temperature
+        );
+    }
+
+    #[test]
+    fn solve_with_threshold_accepting() {
+        let mut annealer = quadratic_annealer(schedule::LinearStepSchedule::new(100.0, 0.0, 10000))
+            .with_acceptance(acceptance::Threshold);
+
+        let state = annealer
+            .anneal_peek::<_, false>(&mut rand::thread_rng())
+            .best_state;
+
+        assert!((state.x - (-5.0)).abs() < 0.1, "{:?}", state);
+    }
+
+    #[test]
+    fn solve_with_late_acceptance() {
+        let new_annealer = || {
+            quadratic_annealer(schedule::LinearStepSchedule::new(0.0, 0.0, 10000))
+                .with_acceptance(LateAcceptance::new(50))
+        };
+
+        let state = new_annealer()
+            .anneal::<_, false>(&mut rand::thread_rng())
+            .best_state;
+        assert!((state.x - (-5.0)).abs() < 0.1, "{:?}", state);
+        let state = new_annealer()
+            .anneal_back::<_, false>(&mut rand::thread_rng())
+            .best_state;
+        assert!((state.x - (-5.0)).abs() < 0.1, "{:?}", state);
+        let state = new_annealer()
+            .anneal_peek::<_, false>(&mut rand::thread_rng())
+            .best_state;
+        assert!((state.x - (-5.0)).abs() < 0.1, "{:?}", state);
+    }
+
+    /// Balance the weights of items in bins, with moves reassigning several items at once.
+    #[derive(Debug, Clone)]
+    struct Bins {
+        assignment: Vec<usize>,
+    }
+
+    struct Items {
+        weights: Vec<f64>,
+        bins: usize,
+    }
+
+    /// (item, bin) pairs, not Copy
+    #[derive(Debug, Clone)]
+    struct Reassign(Vec<(usize, usize)>);
+
+    impl Transition for Reassign {
+        type Context = Items;
+        type State = Bins;
+
+        fn choose<G: Rng>(rng: &mut G, ctx: &Self::Context, _state: &Self::State) -> Self {
+            let k = rng.gen_range(1..=3);
+            Reassign(
+                (0..k)
+                    .map(|_| {
+                        (
+                            rng.gen_range(0..ctx.weights.len()),
+                            rng.gen_range(0..ctx.bins),
+                        )
+                    })
+                    .collect(),
+            )
+        }
+    }
+
+    impl EnergyMeasurable for Bins {
+        type Energy = f64;
+        type Context = Items;
+
+        fn energy(&self, ctx: &Self::Context) -> Self::Energy {
+            let mut loads = vec![0.0; ctx.bins];
+            for (item, &bin) in self.assignment.iter().enumerate() {
+                loads[bin] += ctx.weights[item];
+            }
+            loads.iter().map(|load| load * load).sum()
+        }
+    }
+
+    impl AnnealingState for Bins {
+        type Transition = Reassign;
+
+        fn apply(&mut self, _ctx: &Self::Context, op: &Self::Transition) -> Option<()> {
+            for &(item, bin) in &op.0 {
+                self.assignment[item] = bin;
+            }
+            Some(())
+        }
+    }
+
+    impl AnnealingStatePeeking for Bins {
+        fn peek_energy(
+            &self,
+            ctx: &Self::Context,
+            op: &Self::Transition,
+            _current_energy: Self::Energy,
+        ) -> Option<Self::Energy> {
+            let mut next = self.clone();
+            next.apply(ctx, op)?;
+            Some(next.energy(ctx))
+        }
+    }
+
+    impl AnnealingStateBack for Bins {
+        type Restore = Reassign;
+
+        fn apply_with_restore(
+            &mut self,
+            ctx: &Self::Context,
+            op: &Self::Transition,
+        ) -> Option<Self::Restore> {
+            // undo in reverse order so that repeated items get their first bin back
+            let restore =
+                op.0.iter()
+                    .rev()
+                    .map(|&(item, _)| (item, self.assignment[item]))
+                    .collect();
+            self.apply(ctx, op)?;
+            Some(Reassign(restore))
+        }
+
+        fn back(&mut self, ctx: &Self::Context, restore: &Self::Restore) {
+            self.apply(ctx, restore);
+        }
+    }
+
+    #[test]
+    fn transition_with_heap_data() {
+        use rand::rngs::SmallRng;
+        use rand::SeedableRng;
+
+        let ctx = Items {
+            weights: vec![4.0, 3.0, 3.0, 2.0, 2.0, 2.0],
+            bins: 2,
+        };
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut state = Bins {
+            assignment: vec![0; 6],
+        };
+        test_implementer::run_peeking_and_check(&mut rng, &ctx, &mut state, 100, 6);
+        test_implementer::run_back_and_check(&mut rng, &ctx, &mut state, 100, 6);
+
+        let mut annealer = Annealer::new(
+            Bins {
+                assignment: vec![0; 6],
+            },
+            ctx,
+            schedule::ExponentialStepSchedule::new(100.0, 0.1, 5000),
+        );
+        // loads of 8 and 8
+        let results = [
+            annealer.anneal::<_, false>(&mut rng),
+            annealer.anneal_back::<_, false>(&mut rng),
+            annealer.anneal_peek::<_, false>(&mut rng),
+            annealer.anneal_peek_back::<_, false>(&mut rng),
+        ];
+        for result in results {
+            assert_eq!(result.best_energy, 128.0, "{:?}", result.best_state);
+        }
+    }
 }
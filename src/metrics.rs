@@ -1,14 +1,212 @@
 use std::time::Duration;
 
+use crate::energy::Direction;
+
+/// Record of a step collected when annealing with `METRICS = true`.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Metrics {
+    /// Number of steps done including this one
+    pub step: usize,
     pub best_energy: f64,
+    /// Energy of the current state after the step
     pub current_energy: f64,
+    /// Energy of the candidate state of the step
     pub next_energy: f64,
+    /// Energy difference of the candidate from the state before the step
     pub delta: f64,
     pub accept: bool,
     pub improvement: bool,
+    /// The annealer restarted by its `RestartPolicy` after the step
+    pub restart: bool,
     pub progress: f64,
     pub temperature: f64,
+    /// Serialized as seconds
+    #[cfg_attr(feature = "serde", serde(with = "duration_secs"))]
     pub step_duration: Duration,
 }
+
+/// How metrics are collected when annealing with `METRICS = true`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MetricsConfig {
+    /// Record every step into `Annealer::metrics`
+    #[default]
+    All,
+    /// Record every n-th step (the 1st, (n+1)-th, ...) into `Annealer::metrics`
+    Every(usize),
+    /// Keep only the latest n steps in `Annealer::metrics`, in chronological order.
+    /// Each step shifts the n records, so keep n small.
+    Last(usize),
+    /// Aggregate the steps into `Annealer::metrics_buckets` by temperature,
+    /// with the given number of buckets per decade of temperature
+    PerTemperature(usize),
+}
+
+/// Steps aggregated over a range of temperatures.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MetricsBucket {
+    /// Inclusive lower bound of the temperatures
+    pub temperature_min: f64,
+    /// Exclusive upper bound of the temperatures
+    pub temperature_max: f64,
+    pub steps: usize,
+    pub accepts: usize,
+    pub improvements: usize,
+    pub delta_sum: f64,
+    pub best_energy: f64,
+}
+
+impl MetricsBucket {
+    pub fn acceptance_rate(&self) -> f64 {
+        self.accepts as f64 / self.steps as f64
+    }
+
+    pub fn mean_delta(&self) -> f64 {
+        self.delta_sum / self.steps as f64
+    }
+}
+
+impl MetricsConfig {
+    pub(crate) fn record(
+        &self,
+        records: &mut Vec<Metrics>,
+        buckets: &mut Vec<MetricsBucket>,
+        metrics: Metrics,
+        direction: Direction,
+    ) {
+        match *self {
+            MetricsConfig::All => records.push(metrics),
+            MetricsConfig::Every(n) => {
+                if (metrics.step - 1).is_multiple_of(n.max(1)) {
+                    records.push(metrics);
+                }
+            }
+            MetricsConfig::Last(n) => {
+                // drop the oldest record, so that the records are in chronological order at any time
+                if records.len() < n {
+                    records.push(metrics);
+                } else if n > 0 {
+                    records.rotate_left(1);
+                    records[n - 1] = metrics;
+                }
+            }
+            MetricsConfig::PerTemperature(per_decade) => {
+                let per_decade = per_decade.max(1) as f64;
+                let index = (metrics.temperature.log10() * per_decade).floor();
+                let temperature_min = 10f64.powf(index / per_decade);
+                let bucket = match buckets
+                    .iter()
+                    .rposition(|bucket| bucket.temperature_min == temperature_min)
+                {
+                    Some(position) => &mut buckets[position],
+                    None => {
+                        buckets.push(MetricsBucket {
+                            temperature_min,
+                            temperature_max: 10f64.powf((index + 1.0) / per_decade),
+                            steps: 0,
+                            accepts: 0,
+                            improvements: 0,
+                            delta_sum: 0.0,
+                            best_energy: metrics.best_energy,
+                        });
+                        buckets.last_mut().expect("pushed")
+                    }
+                };
+                bucket.steps += 1;
+                bucket.accepts += metrics.accept as usize;
+                bucket.improvements += metrics.improvement as usize;
+                bucket.delta_sum += metrics.delta;
+                if direction.is_better(metrics.best_energy, bucket.best_energy) {
+                    bucket.best_energy = metrics.best_energy;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+mod duration_secs {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        f64::deserialize(deserializer).map(Duration::from_secs_f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(step: usize, temperature: f64) -> Metrics {
+        Metrics {
+            step,
+            best_energy: -(step as f64),
+            current_energy: 0.0,
+            next_energy: 0.0,
+            delta: 1.0,
+            accept: step.is_multiple_of(2),
+            improvement: false,
+            restart: false,
+            progress: 0.0,
+            temperature,
+            step_duration: Duration::ZERO,
+        }
+    }
+
+    fn record(config: MetricsConfig, steps: usize) -> (Vec<Metrics>, Vec<MetricsBucket>) {
+        let mut records = Vec::new();
+        let mut buckets = Vec::new();
+        for step in 1..=steps {
+            let temperature = 1000.0 / step as f64;
+            config.record(
+                &mut records,
+                &mut buckets,
+                metrics(step, temperature),
+                Direction::Minimize,
+            );
+        }
+        (records, buckets)
+    }
+
+    #[test]
+    fn every_nth_step() {
+        let (records, _) = record(MetricsConfig::Every(10), 100);
+        let steps: Vec<_> = records.iter().map(|m| m.step).collect();
+        assert_eq!(steps, vec![1, 11, 21, 31, 41, 51, 61, 71, 81, 91]);
+    }
+
+    #[test]
+    fn last_steps() {
+        let (records, _) = record(MetricsConfig::Last(3), 5);
+        let steps: Vec<_> = records.iter().map(|m| m.step).collect();
+        assert_eq!(steps, vec![3, 4, 5]);
+
+        let (records, _) = record(MetricsConfig::Last(3), 2);
+        let steps: Vec<_> = records.iter().map(|m| m.step).collect();
+        assert_eq!(steps, vec![1, 2]);
+    }
+
+    #[test]
+    fn per_temperature_buckets() {
+        // temperatures from 1000 down to 1
+        let (records, buckets) = record(MetricsConfig::PerTemperature(1), 1000);
+        assert!(records.is_empty());
+        assert_eq!(buckets.len(), 4);
+        assert_eq!(buckets.iter().map(|b| b.steps).sum::<usize>(), 1000);
+
+        // 1000 / step in [100, 1000) for steps 2..=10
+        let bucket = &buckets[1];
+        assert!((bucket.temperature_min - 100.0).abs() < 1e-9);
+        assert_eq!(bucket.steps, 9);
+        assert_eq!(bucket.accepts, 5);
+        assert_eq!(bucket.mean_delta(), 1.0);
+        assert_eq!(bucket.best_energy, -10.0);
+    }
+}
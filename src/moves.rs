@@ -0,0 +1,102 @@
+use rand::Rng;
+
+use crate::{AnnealingState, Transition};
+
+/// MoveGenerator chooses the moves of the annealer, which owns it alongside the schedule.
+/// Unlike `Transition::choose` it can keep state between steps, e.g. adapt step sizes,
+/// cycle through a neighbourhood or keep a candidate list.
+/// `TransitionMoves` is the default generator calling `Transition::choose`.
+pub trait MoveGenerator<S: AnnealingState> {
+    fn choose<G: Rng>(&mut self, rng: &mut G, ctx: &S::Context, state: &S) -> S::Transition;
+
+    /// Called with the outcome of the move last returned by `choose`.
+    fn feedback(&mut self, _op: &S::Transition, _outcome: MoveOutcome) {}
+
+    /// Continue from the generator saved in a checkpoint by `AnnealRun::checkpoint`.
+    /// Replaces the generator by default, override it when part of the generator is not saved,
+    /// e.g. because it can not be serialized.
+    fn restore(&mut self, saved: Self)
+    where
+        Self: Sized,
+    {
+        *self = saved;
+    }
+}
+
+/// What happened to a move chosen by a `MoveGenerator`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveOutcome {
+    /// The move could not be applied to the state
+    Invalid,
+    Rejected,
+    Accepted {
+        /// Decrease of the cost, i.e. of the energy when minimizing, positive when the move improved
+        gain: f64,
+        /// Whether the move found a new best state
+        new_best: bool,
+    },
+}
+
+/// Moves chosen by `Transition::choose` of the state.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransitionMoves;
+
+impl<S: AnnealingState> MoveGenerator<S> for TransitionMoves {
+    fn choose<G: Rng>(&mut self, rng: &mut G, ctx: &S::Context, state: &S) -> S::Transition {
+        S::Transition::choose(rng, ctx, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::schedule::LinearStepSchedule;
+    use crate::tests::{QuadraticFunction, QuadraticFunctionState, QuadraticFunctionTransition};
+    use crate::Annealer;
+
+    /// Steps of a size which grows on acceptance and shrinks on rejection.
+    struct AdaptiveStep {
+        size: f64,
+    }
+
+    impl MoveGenerator<QuadraticFunctionState> for AdaptiveStep {
+        fn choose<G: Rng>(
+            &mut self,
+            rng: &mut G,
+            _ctx: &QuadraticFunction,
+            _state: &QuadraticFunctionState,
+        ) -> QuadraticFunctionTransition {
+            QuadraticFunctionTransition::Add(rng.gen_range(-self.size..=self.size))
+        }
+
+        fn feedback(&mut self, _op: &QuadraticFunctionTransition, outcome: MoveOutcome) {
+            match outcome {
+                MoveOutcome::Accepted { .. } => self.size *= 1.1,
+                _ => self.size = (self.size * 0.95).max(1e-3),
+            }
+        }
+    }
+
+    #[test]
+    fn stateful_move_generator() {
+        let mut annealer = Annealer::new(
+            QuadraticFunctionState { x: 100.0 },
+            QuadraticFunction {
+                a: 1.0,
+                b: 10.0,
+                c: 30.0,
+            },
+            LinearStepSchedule::new(100.0, 0.01, 5000),
+        )
+        .with_moves(AdaptiveStep { size: 10.0 });
+        let result = annealer.anneal::<_, false>(&mut SmallRng::seed_from_u64(0));
+
+        assert!((result.best_state.x - (-5.0)).abs() < 0.1);
+        // at low temperature most moves are rejected, so the steps shrink
+        assert!(annealer.moves.size < 5.0, "{}", annealer.moves.size);
+    }
+}
@@ -0,0 +1,337 @@
+use std::time::{Duration, Instant};
+
+use rand::{Rng, RngCore, SeedableRng};
+
+use crate::acceptance::{Acceptance, Metropolis};
+use crate::energy::{Direction, Energy};
+use crate::moves::{MoveGenerator, TransitionMoves};
+use crate::run::AnnealResult;
+use crate::schedule::Schedule;
+use crate::{Annealer, AnnealingState, EnergyMeasurable, InitialState};
+
+/// Generate a state from the random number generator of a run and the context.
+pub type GenerateState<S> =
+    Box<dyn Fn(&mut dyn RngCore, &<S as EnergyMeasurable>::Context) -> S + Send + Sync>;
+
+/// Where each run of `MultiStartAnnealer` starts from.
+pub enum StartState<S: EnergyMeasurable> {
+    /// A clone of the state
+    Clone(S),
+    /// A state generated with the random number generator of the run
+    Generate(GenerateState<S>),
+}
+
+impl<S: EnergyMeasurable> StartState<S> {
+    /// Generate the state of each run with `InitialState`.
+    pub fn initial() -> Self
+    where
+        S: InitialState + 'static,
+    {
+        StartState::Generate(Box::new(|mut rng, ctx| S::initial_state(&mut rng, ctx)))
+    }
+
+    fn state(&self, rng: &mut dyn RngCore, ctx: &S::Context) -> S {
+        match self {
+            StartState::Clone(state) => state.clone(),
+            StartState::Generate(generate) => generate(rng, ctx),
+        }
+    }
+}
+
+/// Statistics of a run of `MultiStartAnnealer`.
+#[derive(Debug, Clone)]
+pub struct RunStats {
+    /// Seed of the random number generator of the run
+    pub seed: u64,
+    pub initial_energy: f64,
+    pub best_energy: f64,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct MultiStartResult<S: EnergyMeasurable> {
+    pub best_state: S,
+    pub best_energy: S::Energy,
+    /// Index of the run which found the best state
+    pub best_run: usize,
+    pub runs: Vec<RunStats>,
+}
+
+/// Anneal the same problem `runs` times and keep the best state.
+/// Run i uses a random number generator seeded with `seed + i`, so results are deterministic
+/// whether the runs are sequential or parallel.
+/// Every run anneals with a clone of the acceptance criterion and of the move generator.
+pub struct MultiStartAnnealer<
+    S: EnergyMeasurable,
+    C: Schedule,
+    F: Fn() -> C,
+    A = Metropolis,
+    N = TransitionMoves,
+> {
+    pub start: StartState<S>,
+    pub ctx: S::Context,
+    /// Build the schedule of each run
+    pub schedule: F,
+    pub runs: usize,
+    pub seed: u64,
+    /// Minimize (default) or maximize the energy in every run
+    pub direction: Direction,
+    pub acceptance: A,
+    pub moves: N,
+}
+
+impl<S, C, F> MultiStartAnnealer<S, C, F>
+where
+    S: AnnealingState,
+    C: Schedule,
+    F: Fn() -> C,
+{
+    pub fn new(start: StartState<S>, ctx: S::Context, schedule: F, runs: usize, seed: u64) -> Self {
+        Self {
+            start,
+            ctx,
+            schedule,
+            runs,
+            seed,
+            direction: Direction::Minimize,
+            acceptance: Metropolis,
+            moves: TransitionMoves,
+        }
+    }
+}
+
+impl<S, C, F, A, N> MultiStartAnnealer<S, C, F, A, N>
+where
+    S: AnnealingState,
+    S::Context: Clone,
+    C: Schedule,
+    F: Fn() -> C,
+    A: Acceptance + Clone,
+    N: MoveGenerator<S> + Clone,
+{
+    pub fn with_direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+        self
+    }
+
+    /// Replace the acceptance criterion of every run (Metropolis by default),
+    /// e.g. `LateAcceptance` for Late Acceptance Hill Climbing.
+    pub fn with_acceptance<B: Acceptance + Clone>(
+        self,
+        acceptance: B,
+    ) -> MultiStartAnnealer<S, C, F, B, N> {
+        MultiStartAnnealer {
+            start: self.start,
+            ctx: self.ctx,
+            schedule: self.schedule,
+            runs: self.runs,
+            seed: self.seed,
+            direction: self.direction,
+            acceptance,
+            moves: self.moves,
+        }
+    }
+
+    /// Replace the move generator of every run (`Transition::choose` by default).
+    pub fn with_moves<O: MoveGenerator<S> + Clone>(
+        self,
+        moves: O,
+    ) -> MultiStartAnnealer<S, C, F, A, O> {
+        MultiStartAnnealer {
+            start: self.start,
+            ctx: self.ctx,
+            schedule: self.schedule,
+            runs: self.runs,
+            seed: self.seed,
+            direction: self.direction,
+            acceptance: self.acceptance,
+            moves,
+        }
+    }
+
+    /// Run one after another. `anneal` runs an annealer, e.g. `|annealer, rng| annealer.anneal::<_, false>(rng)`.
+    pub fn run<G, R>(&self, anneal: R) -> MultiStartResult<S>
+    where
+        G: Rng + SeedableRng,
+        R: Fn(&mut Annealer<S, C, A, N>, &mut G) -> AnnealResult<S>,
+    {
+        let runs = (0..self.runs)
+            .map(|run| self.run_one(run, &anneal))
+            .collect();
+        self.best(runs)
+    }
+
+    /// Run in parallel on the rayon thread pool.
+    #[cfg(feature = "rayon")]
+    pub fn run_parallel<G, R>(&self, anneal: R) -> MultiStartResult<S>
+    where
+        S: Send + Sync,
+        S::Energy: Send,
+        S::Context: Sync,
+        F: Sync,
+        A: Sync,
+        N: Sync,
+        G: Rng + SeedableRng,
+        R: Fn(&mut Annealer<S, C, A, N>, &mut G) -> AnnealResult<S> + Sync,
+    {
+        use rayon::prelude::*;
+
+        let runs = (0..self.runs)
+            .into_par_iter()
+            .map(|run| self.run_one(run, &anneal))
+            .collect();
+        self.best(runs)
+    }
+
+    fn run_one<G, R>(&self, run: usize, anneal: &R) -> (S, S::Energy, RunStats)
+    where
+        G: Rng + SeedableRng,
+        R: Fn(&mut Annealer<S, C, A, N>, &mut G) -> AnnealResult<S>,
+    {
+        let start = Instant::now();
+        let seed = self.seed.wrapping_add(run as u64);
+        let mut rng = G::seed_from_u64(seed);
+
+        let state = self.start.state(&mut rng, &self.ctx);
+        let initial_energy = state.energy(&self.ctx);
+        let mut annealer = Annealer::new(state, self.ctx.clone(), (self.schedule)())
+            .with_direction(self.direction)
+            .with_acceptance(self.acceptance.clone())
+            .with_moves(self.moves.clone());
+        let result = anneal(&mut annealer, &mut rng);
+
+        let stats = RunStats {
+            seed,
+            initial_energy: initial_energy.to_f64(),
+            best_energy: result.best_energy.to_f64(),
+            duration: start.elapsed(),
+        };
+        (result.best_state, result.best_energy, stats)
+    }
+
+    fn best(&self, runs: Vec<(S, S::Energy, RunStats)>) -> MultiStartResult<S> {
+        let mut best: Option<(usize, S, S::Energy)> = None;
+        let mut stats = Vec::with_capacity(runs.len());
+        for (run, (state, energy, run_stats)) in runs.into_iter().enumerate() {
+            stats.push(run_stats);
+            if best
+                .as_ref()
+                .is_none_or(|(_, _, best_energy)| self.direction.is_better(energy, *best_energy))
+            {
+                best = Some((run, state, energy));
+            }
+        }
+        let (best_run, best_state, best_energy) = best.expect("runs must be positive");
+        MultiStartResult {
+            best_state,
+            best_energy,
+            best_run,
+            runs: stats,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+
+    use super::*;
+    use crate::acceptance::LateAcceptance;
+    use crate::schedule::LinearStepSchedule;
+    use crate::tests::{QuadraticFunction, QuadraticFunctionState};
+
+    fn multi_start(
+        start: StartState<QuadraticFunctionState>,
+    ) -> MultiStartAnnealer<
+        QuadraticFunctionState,
+        LinearStepSchedule,
+        impl Fn() -> LinearStepSchedule,
+    > {
+        MultiStartAnnealer::new(
+            start,
+            QuadraticFunction {
+                a: 1.0,
+                b: 10.0,
+                c: 30.0,
+            },
+            || LinearStepSchedule::new(1000.0, 0.01, 2000),
+            4,
+            42,
+        )
+    }
+
+    #[test]
+    fn best_of_runs() {
+        let result = multi_start(StartState::Clone(QuadraticFunctionState { x: 100.0 }))
+            .run::<SmallRng, _>(|annealer, rng| annealer.anneal::<_, false>(rng));
+
+        assert_eq!(result.runs.len(), 4);
+        assert!((result.best_state.x - (-5.0)).abs() < 0.1);
+        for (run, stats) in result.runs.iter().enumerate() {
+            assert_eq!(stats.seed, 42 + run as u64);
+            assert_eq!(stats.initial_energy, 11030.0);
+            assert!(result.best_energy <= stats.best_energy);
+        }
+        assert_eq!(result.runs[result.best_run].best_energy, result.best_energy);
+    }
+
+    #[test]
+    fn deterministic_with_generated_start() {
+        let start = || {
+            StartState::Generate(Box::new(
+                |rng: &mut dyn RngCore, _ctx: &QuadraticFunction| QuadraticFunctionState {
+                    x: rng.gen_range(-100.0..100.0),
+                },
+            ))
+        };
+        let first = multi_start(start())
+            .run::<SmallRng, _>(|annealer, rng| annealer.anneal_back::<_, false>(rng));
+        let second = multi_start(start())
+            .run::<SmallRng, _>(|annealer, rng| annealer.anneal_back::<_, false>(rng));
+
+        assert_eq!(first.best_state.x, second.best_state.x);
+        let initial_energies: Vec<_> = first.runs.iter().map(|run| run.initial_energy).collect();
+        assert_ne!(initial_energies[0], initial_energies[1]);
+    }
+
+    #[test]
+    fn initial_start() {
+        let result = multi_start(StartState::initial())
+            .run::<SmallRng, _>(|annealer, rng| annealer.anneal::<_, false>(rng));
+
+        assert!((result.best_state.x - (-5.0)).abs() < 0.1);
+        assert_ne!(result.runs[0].initial_energy, result.runs[1].initial_energy);
+    }
+
+    #[test]
+    fn late_acceptance_runs() {
+        let result = multi_start(StartState::Clone(QuadraticFunctionState { x: 100.0 }))
+            .with_acceptance(LateAcceptance::new(50))
+            .run::<SmallRng, _>(|annealer, rng| annealer.anneal::<_, false>(rng));
+
+        let expected = Annealer::late_acceptance(
+            QuadraticFunctionState { x: 100.0 },
+            QuadraticFunction {
+                a: 1.0,
+                b: 10.0,
+                c: 30.0,
+            },
+            LinearStepSchedule::new(1000.0, 0.01, 2000),
+            50,
+        )
+        .anneal::<_, false>(&mut SmallRng::seed_from_u64(42));
+        assert_eq!(result.runs[0].best_energy, expected.best_energy);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_matches_sequential() {
+        let sequential = multi_start(StartState::Clone(QuadraticFunctionState { x: 100.0 }))
+            .run::<SmallRng, _>(|annealer, rng| annealer.anneal_peek::<_, false>(rng));
+        let parallel = multi_start(StartState::Clone(QuadraticFunctionState { x: 100.0 }))
+            .run_parallel::<SmallRng, _>(|annealer, rng| annealer.anneal_peek::<_, false>(rng));
+
+        assert_eq!(sequential.best_state.x, parallel.best_state.x);
+        assert_eq!(sequential.best_run, parallel.best_run);
+    }
+}
@@ -0,0 +1,72 @@
+use log::info;
+
+use crate::metrics::Metrics;
+use crate::EnergyMeasurable;
+
+/// Summary of a step passed to `Observer::on_step`.
+#[derive(Debug, Clone, Copy)]
+pub struct StepInfo {
+    /// Number of steps done including this one
+    pub step: usize,
+    pub progress: f64,
+    pub temperature: f64,
+    pub current_energy: f64,
+    pub best_energy: f64,
+    pub accept: bool,
+    pub improvement: bool,
+}
+
+/// Observer is notified by the annealer while annealing, e.g. to stream progress to logs or dashboards.
+/// All hooks do nothing by default.
+pub trait Observer<S: EnergyMeasurable> {
+    /// Called after each step
+    fn on_step(&mut self, _info: &StepInfo) {}
+
+    /// Called when a move is accepted with the new current state
+    fn on_accept(&mut self, _state: &S, _energy: S::Energy) {}
+
+    /// Called when the best state is improved
+    fn on_new_best(&mut self, _state: &S, _energy: S::Energy) {}
+
+    /// Called when the annealer restarts by its `RestartPolicy` after the step,
+    /// with the state and energy it continues from
+    fn on_restart(&mut self, _step: usize, _state: &S, _energy: S::Energy) {}
+
+    /// Called after each step with its metrics when annealing with `METRICS = true`,
+    /// regardless of `MetricsConfig`
+    fn on_metrics(&mut self, _metrics: &Metrics) {}
+
+    /// Called once when annealing finished with the best state
+    fn on_finish(&mut self, _best_state: &S, _best_energy: S::Energy) {}
+}
+
+/// Log the progress every `interval` steps with `log::info!`.
+#[derive(Debug, Clone, Copy)]
+pub struct LogObserver {
+    pub interval: usize,
+}
+
+impl LogObserver {
+    pub fn new(interval: usize) -> Self {
+        Self { interval }
+    }
+}
+
+impl<S: EnergyMeasurable> Observer<S> for LogObserver {
+    fn on_step(&mut self, info: &StepInfo) {
+        if info.step.is_multiple_of(self.interval) {
+            info!(
+                "step {} ({:.1}%) temperature {} current {} best {}",
+                info.step,
+                info.progress * 100.0,
+                info.temperature,
+                info.current_energy,
+                info.best_energy
+            );
+        }
+    }
+
+    fn on_finish(&mut self, _best_state: &S, best_energy: S::Energy) {
+        info!("finished with best {:?}", best_energy);
+    }
+}
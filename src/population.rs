@@ -0,0 +1,258 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+
+use crate::energy::Energy;
+use crate::schedule::{Progress, Schedule};
+use crate::tempering::Walker;
+use crate::AnnealingState;
+
+/// Summary of the population at a temperature of the schedule.
+#[derive(Debug, Clone, Copy)]
+pub struct PopulationStep {
+    pub temperature: f64,
+    /// Estimate of ln(Z(T) / Z(T_0)) where Z is the partition function and T_0 the first temperature
+    pub log_partition_ratio: f64,
+    pub mean_energy: f64,
+    pub best_energy: f64,
+    /// Number of states of the initial population which still have descendants
+    pub families: usize,
+}
+
+impl PopulationStep {
+    /// Estimate of the free energy relative to the first temperature, F(T) - F(T_0) T / T_0,
+    /// i.e. -T ln(Z(T) / Z(T_0)).
+    pub fn free_energy(&self) -> f64 {
+        -self.temperature * self.log_partition_ratio
+    }
+}
+
+/// Population annealing.
+/// A population of states is annealed through the temperatures of the schedule.
+/// When the temperature changes from T to T', the population is resampled with weights
+/// exp(-(1/T' - 1/T) E) and each state then walks `sweeps` Metropolis steps at T'.
+/// The mean of the weights estimates the ratio of the partition functions, which gives the free energy.
+pub struct PopulationAnnealer<S: AnnealingState, C: Schedule> {
+    pub population: Vec<S>,
+    pub ctx: S::Context,
+    /// One resampling per step of the schedule
+    pub schedule: C,
+    /// Metropolis steps of each state at each temperature
+    pub sweeps: usize,
+    /// Summary of each temperature of the last run
+    pub steps: Vec<PopulationStep>,
+}
+
+impl<S: AnnealingState, C: Schedule> PopulationAnnealer<S, C> {
+    /// Start with `size` clones of the state.
+    pub fn new(state: S, ctx: S::Context, schedule: C, size: usize, sweeps: usize) -> Self {
+        Self::from_population(vec![state; size], ctx, schedule, sweeps)
+    }
+
+    pub fn from_population(
+        population: Vec<S>,
+        ctx: S::Context,
+        schedule: C,
+        sweeps: usize,
+    ) -> Self {
+        assert!(!population.is_empty(), "population must not be empty");
+        Self {
+            population,
+            ctx,
+            schedule,
+            sweeps,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Anneal the population and return the best state found.
+    /// `population` is replaced with the final population.
+    /// Panics when a temperature of the schedule is not positive.
+    pub fn run<G: Rng>(&mut self, rng: &mut G) -> S {
+        self.steps.clear();
+        let mut walkers: Vec<_> = self
+            .population
+            .iter()
+            .map(|state| Walker::new(state.clone(), &self.ctx))
+            .collect();
+        let mut ancestors: Vec<usize> = (0..walkers.len()).collect();
+        let mut best = (walkers[0].state.clone(), walkers[0].energy);
+        for walker in &walkers[1..] {
+            if walker.energy < best.1 {
+                best = (walker.state.clone(), walker.energy);
+            }
+        }
+
+        let mut log_partition_ratio = 0.0;
+        let mut prev_beta: Option<f64> = None;
+        let mut progress = C::Progress::zero();
+        while self.schedule.should_continue(&progress) {
+            let temperature = self.schedule.temperature(&progress);
+            assert!(
+                temperature > 0.0,
+                "population annealing needs a positive temperature, got {}",
+                temperature
+            );
+            let beta = 1.0 / temperature;
+            if let Some(prev_beta) = prev_beta {
+                let delta_beta = beta - prev_beta;
+                // shift so that the largest weight is 1: by the minimum energy when cooling,
+                // by the maximum energy when heating. The differences to the shift are exact for integer energies.
+                let cooling = delta_beta >= 0.0;
+                let shift = walkers
+                    .iter()
+                    .map(|walker| walker.energy)
+                    .reduce(|shift, energy| {
+                        if (cooling && energy < shift) || (!cooling && energy > shift) {
+                            energy
+                        } else {
+                            shift
+                        }
+                    })
+                    .expect("population must not be empty");
+                let weights: Vec<f64> = walkers
+                    .iter()
+                    .map(|walker| (-delta_beta * walker.energy.delta(shift)).exp())
+                    .collect();
+                let mean_weight = weights.iter().sum::<f64>() / weights.len() as f64;
+                log_partition_ratio += mean_weight.ln() - delta_beta * shift.to_f64();
+
+                let index = WeightedIndex::new(&weights).expect("weights are positive");
+                let resampled: Vec<usize> = (0..walkers.len())
+                    .map(|_| index.sample(&mut *rng))
+                    .collect();
+                walkers = resampled.iter().map(|&i| walkers[i].clone()).collect();
+                ancestors = resampled.iter().map(|&i| ancestors[i]).collect();
+            }
+            prev_beta = Some(beta);
+
+            for walker in walkers.iter_mut() {
+                walker.walk(rng, &self.ctx, temperature, self.sweeps);
+                if walker.best_energy < best.1 {
+                    best = (walker.best_state.clone(), walker.best_energy);
+                }
+            }
+
+            let mut families = ancestors.clone();
+            families.sort_unstable();
+            families.dedup();
+            self.steps.push(PopulationStep {
+                temperature,
+                log_partition_ratio,
+                mean_energy: walkers
+                    .iter()
+                    .map(|walker| walker.energy.to_f64())
+                    .sum::<f64>()
+                    / walkers.len() as f64,
+                best_energy: best.1.to_f64(),
+                families: families.len(),
+            });
+            progress.update();
+        }
+
+        self.population = walkers.into_iter().map(|walker| walker.state).collect();
+        best.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::schedule::{ExponentialStepSchedule, LinearStepSchedule};
+    use crate::tests::{QuadraticFunction, QuadraticFunctionState};
+    use crate::EnergyMeasurable;
+
+    fn quadratic_function() -> QuadraticFunction {
+        QuadraticFunction {
+            a: 1.0,
+            b: 10.0,
+            c: 30.0,
+        }
+    }
+
+    #[test]
+    fn solve_quadratic_function() {
+        let mut annealer = PopulationAnnealer::new(
+            QuadraticFunctionState { x: 100.0 },
+            quadratic_function(),
+            ExponentialStepSchedule::new(1000.0, 0.01, 100),
+            50,
+            10,
+        );
+        let state = annealer.run(&mut SmallRng::seed_from_u64(0));
+
+        assert!((state.x - (-5.0)).abs() < 0.1, "{:?}", state);
+        assert_eq!(annealer.population.len(), 50);
+        assert_eq!(annealer.steps.len(), 100);
+        assert_eq!(annealer.steps[0].log_partition_ratio, 0.0);
+        assert_eq!(annealer.steps[0].families, 50);
+        let last = annealer.steps.last().unwrap();
+        assert!(last.families < 50);
+        assert!(last.mean_energy < annealer.steps[0].mean_energy);
+        assert!((last.best_energy - 5.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn free_energy_of_identical_population() {
+        // without walking every weight is the same, so ln(Z(T) / Z(T_0)) = -(1/T - 1/T_0) E
+        let mut annealer = PopulationAnnealer::new(
+            QuadraticFunctionState { x: 0.0 },
+            quadratic_function(),
+            LinearStepSchedule::new(10.0, 5.0, 2),
+            10,
+            0,
+        );
+        annealer.run(&mut SmallRng::seed_from_u64(0));
+
+        let step = annealer.steps[1];
+        let expected = -(1.0 / step.temperature - 1.0 / 10.0) * 30.0;
+        assert!((step.log_partition_ratio - expected).abs() < 1e-9);
+        assert!((step.free_energy() + step.temperature * expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn heating_does_not_overflow_the_weights() {
+        let population: Vec<_> = (0..10)
+            .map(|i| QuadraticFunctionState { x: 10.0 * i as f64 })
+            .collect();
+        let energies: Vec<f64> = population
+            .iter()
+            .map(|state| state.energy(&quadratic_function()))
+            .collect();
+        let mut annealer = PopulationAnnealer::from_population(
+            population,
+            quadratic_function(),
+            LinearStepSchedule::new(1.0, 1001.0, 2),
+            0,
+        );
+        annealer.run(&mut SmallRng::seed_from_u64(0));
+
+        // ln(mean(exp(-(1/T - 1/T_0) E))) computed with the maximum energy factored out
+        let step = annealer.steps[1];
+        let delta_beta = 1.0 / step.temperature - 1.0;
+        let max_energy = energies.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let mean = energies
+            .iter()
+            .map(|energy| (-delta_beta * (energy - max_energy)).exp())
+            .sum::<f64>()
+            / energies.len() as f64;
+        let expected = mean.ln() - delta_beta * max_energy;
+        assert!(step.log_partition_ratio.is_finite());
+        assert!((step.log_partition_ratio - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    #[should_panic(expected = "positive temperature")]
+    fn zero_temperature() {
+        let mut annealer = PopulationAnnealer::new(
+            QuadraticFunctionState { x: 100.0 },
+            quadratic_function(),
+            ExponentialStepSchedule::new(10.0, 0.0, 10),
+            10,
+            1,
+        );
+        annealer.run(&mut SmallRng::seed_from_u64(0));
+    }
+}
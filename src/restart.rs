@@ -0,0 +1,106 @@
+use rand::RngCore;
+
+use crate::{EnergyMeasurable, InitialState};
+
+/// Generate a fresh state from the random number generator and the context.
+pub type FreshState<S> = fn(&mut dyn RngCore, &<S as EnergyMeasurable>::Context) -> S;
+
+/// What the annealer does when it restarts.
+pub enum RestartStrategy<S: EnergyMeasurable> {
+    /// Continue from the best state found so far
+    Best,
+    /// Raise the temperature to the fraction of the initial temperature of the schedule.
+    /// The raise fades out over the following `after` steps of the policy.
+    Reheat(f64),
+    /// Continue from a freshly generated state
+    Fresh(FreshState<S>),
+}
+
+/// Restart after `after` steps without improving the best energy (or since the last restart).
+pub struct RestartPolicy<S: EnergyMeasurable> {
+    pub after: usize,
+    pub strategy: RestartStrategy<S>,
+}
+
+impl<S: EnergyMeasurable> RestartPolicy<S> {
+    pub fn best(after: usize) -> Self {
+        Self {
+            after,
+            strategy: RestartStrategy::Best,
+        }
+    }
+
+    pub fn reheat(after: usize, fraction: f64) -> Self {
+        Self {
+            after,
+            strategy: RestartStrategy::Reheat(fraction),
+        }
+    }
+
+    /// Restart from a state generated by `InitialState`.
+    pub fn fresh(after: usize) -> Self
+    where
+        S: InitialState,
+    {
+        Self {
+            after,
+            strategy: RestartStrategy::Fresh(|mut rng, ctx| S::initial_state(&mut rng, ctx)),
+        }
+    }
+}
+
+/// Restarts of a run in progress.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct Restarts {
+    pub(crate) last: usize,
+    /// ln of the factor applied to the temperature of the schedule just after reheating
+    log_boost: f64,
+    /// Steps until the reheat fades out
+    remaining: usize,
+    duration: usize,
+}
+
+impl Restarts {
+    /// Factor applied to the temperature of the schedule.
+    pub(crate) fn boost(&self) -> f64 {
+        if self.remaining == 0 {
+            1.0
+        } else {
+            (self.log_boost * self.remaining as f64 / self.duration as f64).exp()
+        }
+    }
+
+    pub(crate) fn reheat(&mut self, boost: f64, duration: usize) {
+        self.log_boost = boost.max(1.0).ln();
+        self.remaining = duration;
+        self.duration = duration;
+    }
+
+    /// Called after each step.
+    pub(crate) fn step(&mut self) {
+        self.remaining = self.remaining.saturating_sub(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reheat_fades_out() {
+        let mut restarts = Restarts::default();
+        assert_eq!(restarts.boost(), 1.0);
+
+        restarts.reheat(100.0, 2);
+        assert!((restarts.boost() - 100.0).abs() < 1e-9);
+        restarts.step();
+        assert!((restarts.boost() - 10.0).abs() < 1e-9);
+        restarts.step();
+        assert_eq!(restarts.boost(), 1.0);
+
+        // never cools down below the schedule
+        restarts.reheat(0.5, 2);
+        assert_eq!(restarts.boost(), 1.0);
+    }
+}
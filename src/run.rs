@@ -0,0 +1,724 @@
+use std::time::{Duration, Instant};
+
+use log::debug;
+use rand::Rng;
+
+use crate::acceptance::Acceptance;
+use crate::checkpoint::Checkpoint;
+use crate::energy::Energy;
+use crate::metrics::Metrics;
+use crate::moves::{MoveGenerator, MoveOutcome};
+use crate::observer::StepInfo;
+use crate::restart::Restarts;
+use crate::schedule::{Progress, Schedule};
+use crate::stop::StopReason;
+use crate::{
+    Annealer, AnnealingState, AnnealingStateBack, AnnealingStatePeeking, EnergyMeasurable,
+};
+
+/// How a step of `AnnealRun` applies the transition to the state.
+pub trait StepMode<S: AnnealingState>: Default {
+    /// What is needed to finish or undo the move
+    type Pending;
+
+    /// Evaluate the transition, returns the energy of the candidate or None when it is not applicable.
+    fn propose(
+        &mut self,
+        state: &mut S,
+        ctx: &S::Context,
+        op: &S::Transition,
+        current_energy: S::Energy,
+    ) -> Option<(S::Energy, Self::Pending)>;
+
+    fn accept(
+        &mut self,
+        state: &mut S,
+        ctx: &S::Context,
+        op: &S::Transition,
+        pending: Self::Pending,
+    );
+
+    fn reject(&mut self, state: &mut S, ctx: &S::Context, pending: Self::Pending);
+
+    /// Called when the current state becomes the best one.
+    /// Returns false when the mode keeps track of the best state instead of the run cloning it.
+    fn improved(&mut self, _state: &S) -> bool {
+        true
+    }
+
+    /// Take the best state kept track of since `improved` returned false, if any.
+    fn take_best(&mut self, _state: &S, _ctx: &S::Context) -> Option<S> {
+        None
+    }
+}
+
+/// Apply the transition to the state and clone the previous state to undo it.
+#[derive(Default)]
+pub struct CloneMode;
+
+impl<S: AnnealingState> StepMode<S> for CloneMode {
+    type Pending = S;
+
+    fn propose(
+        &mut self,
+        state: &mut S,
+        ctx: &S::Context,
+        op: &S::Transition,
+        _current_energy: S::Energy,
+    ) -> Option<(S::Energy, S)> {
+        let prev_state = state.clone();
+        state.apply(ctx, op)?;
+        Some((state.energy(ctx), prev_state))
+    }
+
+    fn accept(&mut self, _state: &mut S, _ctx: &S::Context, _op: &S::Transition, _pending: S) {}
+
+    fn reject(&mut self, state: &mut S, _ctx: &S::Context, pending: S) {
+        *state = pending;
+    }
+}
+
+/// Apply the transition to the state and go back with `AnnealingStateBack` to undo it.
+#[derive(Default)]
+pub struct BackMode;
+
+impl<S: AnnealingStateBack> StepMode<S> for BackMode {
+    type Pending = S::Restore;
+
+    fn propose(
+        &mut self,
+        state: &mut S,
+        ctx: &S::Context,
+        op: &S::Transition,
+        _current_energy: S::Energy,
+    ) -> Option<(S::Energy, S::Restore)> {
+        let restore = state.apply_with_restore(ctx, op)?;
+        Some((state.energy(ctx), restore))
+    }
+
+    fn accept(
+        &mut self,
+        _state: &mut S,
+        _ctx: &S::Context,
+        _op: &S::Transition,
+        _pending: S::Restore,
+    ) {
+    }
+
+    fn reject(&mut self, state: &mut S, ctx: &S::Context, pending: S::Restore) {
+        state.back(ctx, &pending);
+    }
+}
+
+/// Peek the energy with `AnnealingStatePeeking` and apply the transition only when accepted.
+#[derive(Default)]
+pub struct PeekMode;
+
+impl<S: AnnealingStatePeeking> StepMode<S> for PeekMode {
+    type Pending = ();
+
+    fn propose(
+        &mut self,
+        state: &mut S,
+        ctx: &S::Context,
+        op: &S::Transition,
+        current_energy: S::Energy,
+    ) -> Option<(S::Energy, ())> {
+        state
+            .peek_energy(ctx, op, current_energy)
+            .map(|energy| (energy, ()))
+    }
+
+    fn accept(&mut self, state: &mut S, ctx: &S::Context, op: &S::Transition, _pending: ()) {
+        state.apply(ctx, op);
+    }
+
+    fn reject(&mut self, _state: &mut S, _ctx: &S::Context, _pending: ()) {}
+}
+
+/// Peek the energy with `AnnealingStatePeeking` and apply the transition only when accepted,
+/// like `PeekMode`, without cloning the state on every improvement.
+/// After an improvement, accepted moves are applied with `apply_with_restore` and their restores are kept,
+/// so that the best state is cloned and rewound with `back` only when it is needed.
+/// When more than `PEEK_BACK_TRAIL_LIMIT` moves are kept, the best state is rewound and kept instead.
+pub struct PeekBackMode<S: AnnealingStateBack> {
+    /// Restores from the best state to the current state while the best state is not cloned
+    trail: Option<Vec<S::Restore>>,
+    /// The best state rewound when the trail became too long
+    best: Option<S>,
+}
+
+pub const PEEK_BACK_TRAIL_LIMIT: usize = 1024;
+
+impl<S: AnnealingStateBack> Default for PeekBackMode<S> {
+    fn default() -> Self {
+        Self {
+            trail: None,
+            best: None,
+        }
+    }
+}
+
+fn rewind<S: AnnealingStateBack>(state: &S, ctx: &S::Context, trail: &[S::Restore]) -> S {
+    let mut best = state.clone();
+    for restore in trail.iter().rev() {
+        best.back(ctx, restore);
+    }
+    best
+}
+
+impl<S: AnnealingStatePeeking + AnnealingStateBack> StepMode<S> for PeekBackMode<S> {
+    type Pending = ();
+
+    fn propose(
+        &mut self,
+        state: &mut S,
+        ctx: &S::Context,
+        op: &S::Transition,
+        current_energy: S::Energy,
+    ) -> Option<(S::Energy, ())> {
+        state
+            .peek_energy(ctx, op, current_energy)
+            .map(|energy| (energy, ()))
+    }
+
+    fn accept(&mut self, state: &mut S, ctx: &S::Context, op: &S::Transition, _pending: ()) {
+        let Some(trail) = self.trail.as_mut() else {
+            state.apply(ctx, op);
+            return;
+        };
+        if let Some(restore) = state.apply_with_restore(ctx, op) {
+            trail.push(restore);
+        }
+        if trail.len() > PEEK_BACK_TRAIL_LIMIT {
+            self.best = Some(rewind(state, ctx, trail));
+            self.trail = None;
+        }
+    }
+
+    fn reject(&mut self, _state: &mut S, _ctx: &S::Context, _pending: ()) {}
+
+    fn improved(&mut self, _state: &S) -> bool {
+        match self.trail.as_mut() {
+            Some(trail) => trail.clear(),
+            None => self.trail = Some(Vec::new()),
+        }
+        self.best = None;
+        false
+    }
+
+    fn take_best(&mut self, state: &S, ctx: &S::Context) -> Option<S> {
+        match self.trail.take() {
+            Some(trail) => Some(rewind(state, ctx, &trail)),
+            None => self.best.take(),
+        }
+    }
+}
+
+/// Outcome of annealing.
+#[derive(Debug, Clone)]
+pub struct AnnealResult<S: EnergyMeasurable> {
+    pub best_state: S,
+    pub best_energy: S::Energy,
+    pub final_state: S,
+    pub final_energy: S::Energy,
+    /// Number of steps, one move attempted per step
+    pub steps: usize,
+    pub accepted: usize,
+    pub rejected: usize,
+    /// Moves which were not applicable, e.g. `apply` returned None
+    pub invalid: usize,
+    pub elapsed: Duration,
+    pub stop_reason: StopReason,
+}
+
+impl<S: EnergyMeasurable> AnnealResult<S> {
+    pub fn attempted(&self) -> usize {
+        self.accepted + self.rejected + self.invalid
+    }
+
+    /// Accepted moves per attempted move, 0 when no move was attempted
+    pub fn acceptance_rate(&self) -> f64 {
+        if self.attempted() == 0 {
+            return 0.0;
+        }
+        self.accepted as f64 / self.attempted() as f64
+    }
+}
+
+/// Annealing in progress, driven one step at a time with `step`, `run_for` or as an iterator.
+/// Created by `Annealer::start`, `start_back`, `start_peek` and `start_peek_back`.
+/// `finish` runs the remaining steps and returns the result.
+/// `Observer::on_finish` is only called by `finish`, so call it after iterating the run to the end,
+/// e.g. to flush a `CsvMetricsWriter`. A run dropped without `finish` does not call it.
+pub struct AnnealRun<'a, S, C, A, N, G, M, const METRICS: bool>
+where
+    S: AnnealingState,
+    C: Schedule,
+    A: Acceptance,
+    N: MoveGenerator<S>,
+    G: Rng,
+    M: StepMode<S>,
+{
+    annealer: &'a mut Annealer<S, C, A, N>,
+    rng: G,
+    best_state: S,
+    best_energy: S::Energy,
+    current_energy: S::Energy,
+    /// Reference of the energies given to the acceptance criterion
+    initial_energy: S::Energy,
+    progress: C::Progress,
+    step: usize,
+    last_improvement: usize,
+    accepted: usize,
+    rejected: usize,
+    invalid: usize,
+    /// Elapsed time before resuming from a checkpoint
+    elapsed: Duration,
+    started: Instant,
+    stop_reason: Option<StopReason>,
+    mode: M,
+}
+
+impl<'a, S, C, A, N, G, M, const METRICS: bool> AnnealRun<'a, S, C, A, N, G, M, METRICS>
+where
+    S: AnnealingState,
+    C: Schedule,
+    A: Acceptance,
+    N: MoveGenerator<S>,
+    G: Rng,
+    M: StepMode<S>,
+{
+    pub(crate) fn new(annealer: &'a mut Annealer<S, C, A, N>, rng: G) -> Self {
+        let best_energy = annealer.state.energy(&annealer.ctx);
+        // energies given to the acceptance are relative to the initial energy
+        annealer.acceptance.init(0.0);
+        annealer.restarts = Restarts::default();
+        if METRICS {
+            annealer.clear_metrics();
+        }
+        Self {
+            best_state: annealer.state.clone(),
+            annealer,
+            rng,
+            best_energy,
+            current_energy: best_energy,
+            initial_energy: best_energy,
+            progress: Progress::zero(),
+            step: 0,
+            last_improvement: 0,
+            accepted: 0,
+            rejected: 0,
+            invalid: 0,
+            elapsed: Duration::ZERO,
+            started: Instant::now(),
+            stop_reason: None,
+            mode: M::default(),
+        }
+    }
+
+    /// Continue from the checkpoint, replacing the state, schedule and acceptance criterion of the annealer.
+    pub(crate) fn resume(
+        annealer: &'a mut Annealer<S, C, A, N>,
+        checkpoint: Checkpoint<S, C, A, G, N>,
+    ) -> Self {
+        annealer.state = checkpoint.state;
+        annealer.schedule = checkpoint.schedule;
+        annealer.acceptance = checkpoint.acceptance;
+        annealer.restarts = checkpoint.restarts;
+        annealer.moves.restore(checkpoint.moves);
+        if METRICS {
+            annealer.clear_metrics();
+        }
+        Self {
+            annealer,
+            rng: checkpoint.rng,
+            best_state: checkpoint.best_state,
+            best_energy: checkpoint.best_energy,
+            current_energy: checkpoint.current_energy,
+            initial_energy: checkpoint.initial_energy,
+            progress: checkpoint.progress,
+            step: checkpoint.step,
+            last_improvement: checkpoint.last_improvement,
+            accepted: checkpoint.accepted,
+            rejected: checkpoint.rejected,
+            invalid: checkpoint.invalid,
+            elapsed: checkpoint.elapsed,
+            started: Instant::now(),
+            stop_reason: None,
+            mode: M::default(),
+        }
+    }
+
+    /// Capture the run to continue it later with `Annealer::resume`.
+    pub fn checkpoint(&mut self) -> Checkpoint<S, C, A, G, N>
+    where
+        C: Clone,
+        C::Progress: Clone,
+        A: Clone,
+        G: Clone,
+        N: Clone,
+    {
+        self.sync_best();
+        Checkpoint {
+            progress: self.progress.clone(),
+            step: self.step,
+            last_improvement: self.last_improvement,
+            accepted: self.accepted,
+            rejected: self.rejected,
+            invalid: self.invalid,
+            elapsed: self.elapsed(),
+            state: self.annealer.state.clone(),
+            current_energy: self.current_energy,
+            initial_energy: self.initial_energy,
+            best_state: self.best_state.clone(),
+            best_energy: self.best_energy,
+            schedule: self.annealer.schedule.clone(),
+            acceptance: self.annealer.acceptance.clone(),
+            rng: self.rng.clone(),
+            moves: self.annealer.moves.clone(),
+            restarts: self.annealer.restarts,
+        }
+    }
+
+    /// Do a step, returns None when the schedule ended or a stop condition is met.
+    pub fn step(&mut self) -> Option<StepInfo> {
+        if self.stop_reason.is_some() {
+            return None;
+        }
+        if !self.annealer.schedule.should_continue(&self.progress) {
+            self.stop_reason = Some(StopReason::Schedule);
+            return None;
+        }
+        if let Some(reason) = self.annealer.check_stop(
+            self.step,
+            self.last_improvement,
+            self.current_energy,
+            self.best_energy,
+        ) {
+            debug!("stop {:?}", reason);
+            self.stop_reason = Some(reason);
+            return None;
+        }
+        self.step += 1;
+
+        let start = if METRICS { Some(Instant::now()) } else { None };
+        let annealer = &mut *self.annealer;
+        let temperature = annealer.temperature(&self.progress);
+        let prev_energy = self.current_energy;
+        let mut next_energy = self.current_energy;
+        let op = annealer
+            .moves
+            .choose(&mut self.rng, &annealer.ctx, &annealer.state);
+
+        let outcome = if let Some((new_energy, pending)) =
+            self.mode
+                .propose(&mut annealer.state, &annealer.ctx, &op, self.current_energy)
+        {
+            next_energy = new_energy;
+            let direction = annealer.direction;
+            let delta = direction.cost_delta(new_energy, self.current_energy);
+            let accept = annealer.acceptance.accept(
+                &mut self.rng,
+                direction.cost_delta(self.current_energy, self.initial_energy),
+                delta,
+                direction.cost_delta(self.best_energy, self.initial_energy),
+                temperature,
+            );
+            annealer.schedule.feedback(&self.progress, accept);
+            if accept {
+                debug!(
+                    "accept {} -> {}",
+                    self.current_energy.to_f64(),
+                    new_energy.to_f64()
+                );
+                self.mode
+                    .accept(&mut annealer.state, &annealer.ctx, &op, pending);
+                self.accepted += 1;
+                self.current_energy = new_energy;
+                for observer in &mut annealer.observers {
+                    observer.on_accept(&annealer.state, new_energy);
+                }
+                let new_best = annealer.direction.is_better(new_energy, self.best_energy);
+                if new_best {
+                    self.best_energy = new_energy;
+                    if self.mode.improved(&annealer.state) {
+                        self.best_state = annealer.state.clone();
+                    }
+                    self.last_improvement = self.step;
+                    for observer in &mut annealer.observers {
+                        observer.on_new_best(&annealer.state, new_energy);
+                    }
+                }
+                MoveOutcome::Accepted {
+                    gain: -delta,
+                    new_best,
+                }
+            } else {
+                debug!(
+                    "reject {} -> {}",
+                    self.current_energy.to_f64(),
+                    new_energy.to_f64()
+                );
+                self.mode
+                    .reject(&mut annealer.state, &annealer.ctx, pending);
+                self.rejected += 1;
+                MoveOutcome::Rejected
+            }
+        } else {
+            self.invalid += 1;
+            MoveOutcome::Invalid
+        };
+        let (accept, improvement) = match outcome {
+            MoveOutcome::Accepted { new_best, .. } => (true, new_best),
+            _ => (false, false),
+        };
+        annealer.moves.feedback(&op, outcome);
+
+        let restart = annealer.restart_due(self.step, self.last_improvement);
+        if restart {
+            if let Some(best_state) = self.mode.take_best(&annealer.state, &annealer.ctx) {
+                self.best_state = best_state;
+            }
+            annealer.restart_by_policy(
+                &mut self.rng,
+                &self.progress,
+                self.step,
+                (&self.best_state, self.best_energy),
+                &mut self.current_energy,
+                self.initial_energy,
+            );
+        }
+
+        let info = StepInfo {
+            step: self.step,
+            progress: annealer.schedule.progress_0_1(&self.progress),
+            temperature,
+            current_energy: self.current_energy.to_f64(),
+            best_energy: self.best_energy.to_f64(),
+            accept,
+            improvement,
+        };
+        if METRICS {
+            annealer.record_metrics(Metrics {
+                step: self.step,
+                best_energy: info.best_energy,
+                current_energy: info.current_energy,
+                next_energy: next_energy.to_f64(),
+                delta: next_energy.delta(prev_energy),
+                accept,
+                improvement,
+                restart,
+                progress: info.progress,
+                temperature,
+                step_duration: start.expect("METRICS = true").elapsed(),
+            });
+        }
+        for observer in &mut annealer.observers {
+            observer.on_step(&info);
+        }
+
+        self.progress.update();
+        Some(info)
+    }
+
+    /// Do up to `n` steps, returns the number of steps done.
+    pub fn run_for(&mut self, n: usize) -> usize {
+        (0..n).take_while(|_| self.step().is_some()).count()
+    }
+
+    /// Run the remaining steps, call `Observer::on_finish` and return the result.
+    pub fn finish(mut self) -> AnnealResult<S> {
+        while self.step().is_some() {}
+        self.sync_best();
+        for observer in &mut self.annealer.observers {
+            observer.on_finish(&self.best_state, self.best_energy);
+        }
+        AnnealResult {
+            final_state: self.annealer.state.clone(),
+            final_energy: self.current_energy,
+            steps: self.step,
+            accepted: self.accepted,
+            rejected: self.rejected,
+            invalid: self.invalid,
+            elapsed: self.elapsed(),
+            stop_reason: self.stop_reason.expect("finished"),
+            best_state: self.best_state,
+            best_energy: self.best_energy,
+        }
+    }
+
+    /// Take the best state kept track of by the mode.
+    fn sync_best(&mut self) {
+        if let Some(best_state) = self
+            .mode
+            .take_best(&self.annealer.state, &self.annealer.ctx)
+        {
+            self.best_state = best_state;
+        }
+    }
+
+    /// Why the run ended, None while running.
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.stop_reason
+    }
+
+    /// Time spent so far, including the time before the checkpoint when resumed
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed + self.started.elapsed()
+    }
+
+    /// Number of steps done
+    pub fn steps(&self) -> usize {
+        self.step
+    }
+
+    pub fn progress(&self) -> f64 {
+        self.annealer.schedule.progress_0_1(&self.progress)
+    }
+
+    pub fn state(&self) -> &S {
+        &self.annealer.state
+    }
+
+    pub fn current_energy(&self) -> S::Energy {
+        self.current_energy
+    }
+
+    pub fn best_state(&mut self) -> &S {
+        self.sync_best();
+        &self.best_state
+    }
+
+    pub fn best_energy(&self) -> S::Energy {
+        self.best_energy
+    }
+}
+
+impl<S, C, A, N, G, M, const METRICS: bool> Iterator for AnnealRun<'_, S, C, A, N, G, M, METRICS>
+where
+    S: AnnealingState,
+    C: Schedule,
+    A: Acceptance,
+    N: MoveGenerator<S>,
+    G: Rng,
+    M: StepMode<S>,
+{
+    type Item = StepInfo;
+
+    fn next(&mut self) -> Option<StepInfo> {
+        self.step()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::metrics::MetricsConfig;
+    use crate::schedule::LinearStepSchedule;
+    use crate::stop::TargetEnergy;
+    use crate::tests::{
+        quadratic_annealer, QuadraticFunction, QuadraticFunctionState, QuadraticFunctionTransition,
+    };
+
+    fn annealer() -> Annealer<QuadraticFunctionState, LinearStepSchedule> {
+        quadratic_annealer(LinearStepSchedule::new(1000.0, 0.01, 1000))
+    }
+
+    #[test]
+    fn step_by_step_matches_anneal() {
+        let expected = annealer()
+            .anneal_back::<_, false>(&mut SmallRng::seed_from_u64(0))
+            .best_state;
+
+        let mut annealer = annealer();
+        let mut run = annealer.start_back::<_, true>(SmallRng::seed_from_u64(0));
+        assert_eq!(run.run_for(300), 300);
+        assert_eq!(run.steps(), 300);
+        assert!((run.progress() - 0.3).abs() < 1e-9);
+        assert!(run.best_energy() <= run.current_energy());
+        let info = run.step().unwrap();
+        assert_eq!(info.step, 301);
+        assert_eq!(run.by_ref().count(), 699);
+        assert_eq!(run.stop_reason(), Some(StopReason::Schedule));
+        assert_eq!(run.run_for(10), 0);
+        let result = run.finish();
+
+        assert_eq!(result.best_state.x, expected.x);
+        assert_eq!(result.steps, 1000);
+        assert_eq!(result.attempted(), 1000);
+        assert_eq!(result.invalid, 0);
+        assert_eq!(result.stop_reason, StopReason::Schedule);
+        assert_eq!(
+            result.final_energy,
+            result.final_state.energy(&annealer.ctx)
+        );
+        assert_eq!(result.best_energy, result.best_state.energy(&annealer.ctx));
+        assert_eq!(
+            result.accepted,
+            annealer.metrics.iter().filter(|m| m.accept).count()
+        );
+    }
+
+    #[test]
+    fn last_metrics_are_chronological_without_finish() {
+        let mut annealer = annealer().with_metrics_config(MetricsConfig::Last(3));
+        for _ in annealer.start::<_, true>(SmallRng::seed_from_u64(0)) {}
+
+        let steps: Vec<_> = annealer.metrics.iter().map(|m| m.step).collect();
+        assert_eq!(steps, [998, 999, 1000]);
+    }
+
+    #[test]
+    fn stop_condition_ends_run() {
+        let mut annealer = annealer().with_stop_condition(TargetEnergy::new(100.0));
+        let mut run = annealer.start::<_, false>(rand::thread_rng());
+        let steps = run.run_for(1000);
+
+        assert!(steps < 1000);
+        assert_eq!(run.stop_reason(), Some(StopReason::TargetEnergy));
+        assert!(run.best_energy() <= 100.0);
+        assert!(run.finish().best_state.x < 100.0);
+    }
+
+    #[test]
+    fn peek_back_matches_peek() {
+        let expected = annealer().anneal_peek::<_, true>(&mut SmallRng::seed_from_u64(0));
+
+        let mut annealer = annealer();
+        let result = annealer.anneal_peek_back::<_, true>(&mut SmallRng::seed_from_u64(0));
+
+        assert_eq!(result.best_state.x, expected.best_state.x);
+        assert_eq!(result.best_energy, expected.best_energy);
+        assert_eq!(result.final_state.x, expected.final_state.x);
+        assert_eq!(result.accepted, expected.accepted);
+    }
+
+    #[test]
+    fn peek_back_rewinds_long_trail() {
+        let ctx = QuadraticFunction {
+            a: 1.0,
+            b: 10.0,
+            c: 30.0,
+        };
+        let mut state = QuadraticFunctionState { x: -5.0 };
+        let mut mode = PeekBackMode::default();
+        assert!(!mode.improved(&state));
+
+        let op = QuadraticFunctionTransition::Add(1.0);
+        mode.accept(&mut state, &ctx, &op, ());
+        assert_eq!(mode.take_best(&state, &ctx).unwrap().x, -5.0);
+        assert!(mode.take_best(&state, &ctx).is_none());
+
+        assert!(!mode.improved(&state));
+        for _ in 0..PEEK_BACK_TRAIL_LIMIT + 10 {
+            mode.accept(&mut state, &ctx, &op, ());
+        }
+        assert!(mode.trail.is_none());
+        assert_eq!(mode.take_best(&state, &ctx).unwrap().x, -4.0);
+        assert_eq!(state.x, -4.0 + (PEEK_BACK_TRAIL_LIMIT + 10) as f64);
+    }
+}
@@ -7,6 +7,10 @@ pub trait Schedule {
 
     fn should_continue(&self, progress: &Self::Progress) -> bool;
     fn temperature(&self, progress: &Self::Progress) -> f64;
+
+    /// Called by the annealer after each evaluated move with whether it was accepted.
+    /// Schedules which adjust the temperature online override this.
+    fn feedback(&mut self, _progress: &Self::Progress, _accepted: bool) {}
 }
 
 pub trait Progress {
@@ -18,6 +22,7 @@ pub trait Progress {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Step(pub usize);
 
 impl Progress for Step {
@@ -36,6 +41,7 @@ impl Progress for Step {
     }
 }
 
+/// Serialized as the elapsed time in seconds, so that it continues from there when deserialized.
 #[derive(Debug, Clone, Copy)]
 pub struct Time {
     start: Instant,
@@ -64,6 +70,59 @@ impl Progress for Time {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Time {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64((self.current - self.start).as_secs_f64())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Time {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let elapsed = Duration::from_secs_f64(f64::deserialize(deserializer)?);
+        let current = Instant::now();
+        let start = current
+            .checked_sub(elapsed)
+            .ok_or_else(|| serde::de::Error::custom("elapsed time out of range"))?;
+        Ok(Self { start, current })
+    }
+}
+
+/// Step and Time progress together for budgets of "N steps or T time, whichever comes first".
+/// The progress is the one of whichever is further along.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StepTime {
+    pub step: Step,
+    pub time: Time,
+}
+
+impl Progress for StepTime {
+    type Maximum = (usize, Duration);
+
+    fn zero() -> Self {
+        Self {
+            step: Step::zero(),
+            time: Time::zero(),
+        }
+    }
+
+    fn update(&mut self) {
+        self.step.update();
+        self.time.update();
+    }
+
+    fn progress(&self, maximum: Self::Maximum) -> f64 {
+        let (max_steps, max_time) = maximum;
+        self.step
+            .progress(max_steps)
+            .max(self.time.progress(max_time))
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LinearStepSchedule {
     pub t_max: f64,
     pub t_min: f64,
@@ -97,6 +156,8 @@ impl Schedule for LinearStepSchedule {
     }
 }
 
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LinearTimeSchedule {
     pub t_max: f64,
     pub t_min: f64,
@@ -130,6 +191,218 @@ impl Schedule for LinearTimeSchedule {
     }
 }
 
+/// T = t_max * (t_min / t_max) ^ progress
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExponentialStepSchedule {
+    pub t_max: f64,
+    pub t_min: f64,
+    pub max_steps: usize,
+}
+
+impl ExponentialStepSchedule {
+    pub fn new(tmax: f64, tmin: f64, max_steps: usize) -> Self {
+        Self {
+            t_max: tmax,
+            t_min: tmin,
+            max_steps,
+        }
+    }
+}
+
+impl Schedule for ExponentialStepSchedule {
+    type Progress = Step;
+
+    fn progress_0_1(&self, progress: &Self::Progress) -> f64 {
+        progress.progress(self.max_steps)
+    }
+
+    fn should_continue(&self, progress: &Self::Progress) -> bool {
+        progress.0 < self.max_steps
+    }
+
+    fn temperature(&self, progress: &Self::Progress) -> f64 {
+        let progress = progress.progress(self.max_steps);
+        self.t_max * (self.t_min / self.t_max).powf(progress)
+    }
+}
+
+/// T = t_max * (t_min / t_max) ^ progress
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExponentialTimeSchedule {
+    pub t_max: f64,
+    pub t_min: f64,
+    pub max_time: Duration,
+}
+
+impl ExponentialTimeSchedule {
+    pub fn new(tmax: f64, tmin: f64, max_time: Duration) -> Self {
+        Self {
+            t_max: tmax,
+            t_min: tmin,
+            max_time,
+        }
+    }
+}
+
+impl Schedule for ExponentialTimeSchedule {
+    type Progress = Time;
+
+    fn progress_0_1(&self, progress: &Self::Progress) -> f64 {
+        progress.progress(self.max_time)
+    }
+
+    fn should_continue(&self, progress: &Self::Progress) -> bool {
+        progress.current - progress.start < self.max_time
+    }
+
+    fn temperature(&self, progress: &Self::Progress) -> f64 {
+        let progress = progress.progress(self.max_time);
+        self.t_max * (self.t_min / self.t_max).powf(progress)
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinearStepTimeSchedule {
+    pub t_max: f64,
+    pub t_min: f64,
+    pub max_steps: usize,
+    pub max_time: Duration,
+}
+
+impl LinearStepTimeSchedule {
+    pub fn new(tmax: f64, tmin: f64, max_steps: usize, max_time: Duration) -> Self {
+        Self {
+            t_max: tmax,
+            t_min: tmin,
+            max_steps,
+            max_time,
+        }
+    }
+}
+
+impl Schedule for LinearStepTimeSchedule {
+    type Progress = StepTime;
+
+    fn progress_0_1(&self, progress: &Self::Progress) -> f64 {
+        progress.progress((self.max_steps, self.max_time))
+    }
+
+    fn should_continue(&self, progress: &Self::Progress) -> bool {
+        progress.step.0 < self.max_steps
+            && progress.time.current - progress.time.start < self.max_time
+    }
+
+    fn temperature(&self, progress: &Self::Progress) -> f64 {
+        let progress = progress.progress((self.max_steps, self.max_time));
+        self.t_max - (self.t_max - self.t_min) * progress
+    }
+}
+
+/// T = t_max * (t_min / t_max) ^ progress
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExponentialStepTimeSchedule {
+    pub t_max: f64,
+    pub t_min: f64,
+    pub max_steps: usize,
+    pub max_time: Duration,
+}
+
+impl ExponentialStepTimeSchedule {
+    pub fn new(tmax: f64, tmin: f64, max_steps: usize, max_time: Duration) -> Self {
+        Self {
+            t_max: tmax,
+            t_min: tmin,
+            max_steps,
+            max_time,
+        }
+    }
+}
+
+impl Schedule for ExponentialStepTimeSchedule {
+    type Progress = StepTime;
+
+    fn progress_0_1(&self, progress: &Self::Progress) -> f64 {
+        progress.progress((self.max_steps, self.max_time))
+    }
+
+    fn should_continue(&self, progress: &Self::Progress) -> bool {
+        progress.step.0 < self.max_steps
+            && progress.time.current - progress.time.start < self.max_time
+    }
+
+    fn temperature(&self, progress: &Self::Progress) -> f64 {
+        let progress = progress.progress((self.max_steps, self.max_time));
+        self.t_max * (self.t_min / self.t_max).powf(progress)
+    }
+}
+
+/// Adjust the temperature online so that the acceptance rate tracks a target curve.
+/// The target acceptance rate moves geometrically from `target_start` to `target_end` over the run.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdaptiveStepSchedule {
+    pub target_start: f64,
+    pub target_end: f64,
+    pub max_steps: usize,
+    /// Gain of the multiplicative temperature update per step
+    pub gain: f64,
+    /// Weight of the latest move in the moving average of the acceptance rate
+    pub smoothing: f64,
+    pub temperature: f64,
+    pub acceptance_rate: f64,
+}
+
+impl AdaptiveStepSchedule {
+    pub fn new(
+        initial_temperature: f64,
+        target_start: f64,
+        target_end: f64,
+        max_steps: usize,
+    ) -> Self {
+        Self {
+            target_start,
+            target_end,
+            max_steps,
+            gain: 0.01,
+            smoothing: 0.01,
+            temperature: initial_temperature,
+            acceptance_rate: target_start,
+        }
+    }
+
+    /// Target acceptance rate at the progress
+    pub fn target(&self, progress: &Step) -> f64 {
+        let progress = progress.progress(self.max_steps);
+        self.target_start * (self.target_end / self.target_start).powf(progress)
+    }
+}
+
+impl Schedule for AdaptiveStepSchedule {
+    type Progress = Step;
+
+    fn progress_0_1(&self, progress: &Self::Progress) -> f64 {
+        progress.progress(self.max_steps)
+    }
+
+    fn should_continue(&self, progress: &Self::Progress) -> bool {
+        progress.0 < self.max_steps
+    }
+
+    fn temperature(&self, _progress: &Self::Progress) -> f64 {
+        self.temperature
+    }
+
+    fn feedback(&mut self, progress: &Self::Progress, accepted: bool) {
+        let accepted = if accepted { 1.0 } else { 0.0 };
+        self.acceptance_rate += self.smoothing * (accepted - self.acceptance_rate);
+        self.temperature *= (self.gain * (self.target(progress) - self.acceptance_rate)).exp();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::thread::sleep;
@@ -150,6 +423,101 @@ mod tests {
         assert_eq!(scheduler.temperature(&progress), 0.5);
     }
 
+    #[test]
+    fn exponential_step_scheduler() {
+        let scheduler = ExponentialStepSchedule::new(100.0, 1.0, 10);
+        let mut progress = Step::zero();
+        assert_eq!(scheduler.temperature(&progress), 100.0);
+
+        progress.update();
+        progress.update();
+        progress.update();
+        progress.update();
+        progress.update();
+
+        assert!(scheduler.should_continue(&progress));
+        assert!((scheduler.temperature(&progress) - 10.0).abs() < 1e-9);
+
+        let progress = Step(10);
+        assert!(!scheduler.should_continue(&progress));
+        assert!((scheduler.temperature(&progress) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn exponential_time_scheduler() {
+        let scheduler = ExponentialTimeSchedule::new(100.0, 1.0, Duration::from_millis(100));
+        let mut progress = Time {
+            start: Instant::now(),
+            current: Instant::now(),
+        };
+        sleep(Duration::from_millis(50));
+        progress.update();
+
+        assert!(scheduler.should_continue(&progress));
+        assert!(
+            5.0 < scheduler.temperature(&progress) && scheduler.temperature(&progress) < 20.0,
+            "Temperature: {}",
+            scheduler.temperature(&progress)
+        );
+    }
+
+    #[test]
+    fn linear_step_time_scheduler_step_bound() {
+        let scheduler = LinearStepTimeSchedule::new(1.0, 0.0, 10, Duration::from_secs(3600));
+        let mut progress = StepTime::zero();
+        for _ in 0..5 {
+            progress.update();
+        }
+
+        assert!(scheduler.should_continue(&progress));
+        assert!((scheduler.temperature(&progress) - 0.5).abs() < 1e-3);
+
+        for _ in 0..5 {
+            progress.update();
+        }
+        assert!(!scheduler.should_continue(&progress));
+    }
+
+    #[test]
+    fn exponential_step_time_scheduler_time_bound() {
+        let scheduler =
+            ExponentialStepTimeSchedule::new(100.0, 1.0, 1_000_000, Duration::from_millis(100));
+        let mut progress = StepTime::zero();
+        sleep(Duration::from_millis(50));
+        progress.update();
+
+        assert!(scheduler.should_continue(&progress));
+        assert!(
+            5.0 < scheduler.temperature(&progress) && scheduler.temperature(&progress) < 20.0,
+            "Temperature: {}",
+            scheduler.temperature(&progress)
+        );
+
+        sleep(Duration::from_millis(50));
+        progress.update();
+        assert!(!scheduler.should_continue(&progress));
+    }
+
+    #[test]
+    fn adaptive_step_scheduler() {
+        let mut scheduler = AdaptiveStepSchedule::new(1.0, 0.8, 0.01, 100);
+        assert!((scheduler.target(&Step(0)) - 0.8).abs() < 1e-9);
+        assert!((scheduler.target(&Step(100)) - 0.01).abs() < 1e-9);
+
+        // too few acceptances heat up
+        for _ in 0..50 {
+            scheduler.feedback(&Step(0), false);
+        }
+        assert!(scheduler.temperature(&Step(0)) > 1.0);
+
+        // too many acceptances cool down
+        let mut scheduler = AdaptiveStepSchedule::new(1.0, 0.8, 0.01, 100);
+        for _ in 0..50 {
+            scheduler.feedback(&Step(90), true);
+        }
+        assert!(scheduler.temperature(&Step(90)) < 1.0);
+    }
+
     #[test]
     fn linear_time_scheduler() {
         let scheduler = super::LinearTimeSchedule::new(1.0, 0.0, Duration::from_millis(100));
@@ -167,4 +535,17 @@ mod tests {
             scheduler.temperature(&progress)
         );
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn time_progress_continues_after_deserialize() {
+        let mut progress = Time::zero();
+        sleep(Duration::from_millis(20));
+        progress.update();
+        let json = serde_json::to_string(&progress).unwrap();
+        let restored: Time = serde_json::from_str(&json).unwrap();
+
+        let max_time = Duration::from_millis(100);
+        assert!((restored.progress(max_time) - progress.progress(max_time)).abs() < 1e-6);
+    }
 }
@@ -0,0 +1,206 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::energy::Direction;
+
+/// Snapshot of the annealing passed to the stop conditions before each step.
+#[derive(Debug, Clone, Copy)]
+pub struct AnnealStatus {
+    /// Number of steps done
+    pub step: usize,
+    pub current_energy: f64,
+    pub best_energy: f64,
+    /// Number of steps since the best energy was last improved
+    pub steps_since_improvement: usize,
+    pub direction: Direction,
+}
+
+/// Why annealing stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+    /// The schedule finished
+    Schedule,
+    TargetEnergy,
+    Stagnation,
+    Cancelled,
+    /// A user defined stop condition
+    Custom,
+}
+
+/// StopCondition is checked by the annealer before each step to stop earlier than the schedule.
+pub trait StopCondition {
+    /// Return the reason to stop, or None to continue.
+    fn check(&mut self, status: &AnnealStatus) -> Option<StopReason>;
+
+    /// Stop when either condition is met.
+    fn or<T: StopCondition>(self, other: T) -> Or<Self, T>
+    where
+        Self: Sized,
+    {
+        Or(self, other)
+    }
+
+    /// Stop when both conditions are met. The reason of `other` is reported.
+    fn and<T: StopCondition>(self, other: T) -> And<Self, T>
+    where
+        Self: Sized,
+    {
+        And(self, other)
+    }
+}
+
+/// A closure returning true to stop, reported as `StopReason::Custom`.
+impl<F: FnMut(&AnnealStatus) -> bool> StopCondition for F {
+    fn check(&mut self, status: &AnnealStatus) -> Option<StopReason> {
+        self(status).then_some(StopReason::Custom)
+    }
+}
+
+/// Stop when the best energy reaches the target, from above when minimizing or from below when maximizing.
+#[derive(Debug, Clone, Copy)]
+pub struct TargetEnergy {
+    pub target: f64,
+}
+
+impl TargetEnergy {
+    pub fn new(target: f64) -> Self {
+        Self { target }
+    }
+}
+
+impl StopCondition for TargetEnergy {
+    fn check(&mut self, status: &AnnealStatus) -> Option<StopReason> {
+        let reached = !status.direction.is_better(self.target, status.best_energy);
+        reached.then_some(StopReason::TargetEnergy)
+    }
+}
+
+/// Stop when the best energy has not been improved for `steps` steps.
+#[derive(Debug, Clone, Copy)]
+pub struct Stagnation {
+    pub steps: usize,
+}
+
+impl Stagnation {
+    pub fn new(steps: usize) -> Self {
+        Self { steps }
+    }
+}
+
+impl StopCondition for Stagnation {
+    fn check(&mut self, status: &AnnealStatus) -> Option<StopReason> {
+        (status.steps_since_improvement >= self.steps).then_some(StopReason::Stagnation)
+    }
+}
+
+/// Stop when the flag is set, e.g. from a Ctrl-C handler or another thread.
+#[derive(Debug, Clone)]
+pub struct Cancellation {
+    pub flag: Arc<AtomicBool>,
+}
+
+impl Cancellation {
+    pub fn new(flag: Arc<AtomicBool>) -> Self {
+        Self { flag }
+    }
+}
+
+impl StopCondition for Cancellation {
+    fn check(&mut self, _status: &AnnealStatus) -> Option<StopReason> {
+        self.flag
+            .load(Ordering::Relaxed)
+            .then_some(StopReason::Cancelled)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Or<A, B>(pub A, pub B);
+
+impl<A: StopCondition, B: StopCondition> StopCondition for Or<A, B> {
+    fn check(&mut self, status: &AnnealStatus) -> Option<StopReason> {
+        self.0.check(status).or_else(|| self.1.check(status))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct And<A, B>(pub A, pub B);
+
+impl<A: StopCondition, B: StopCondition> StopCondition for And<A, B> {
+    fn check(&mut self, status: &AnnealStatus) -> Option<StopReason> {
+        let first = self.0.check(status);
+        let second = self.1.check(status);
+        first.and(second)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(best_energy: f64, steps_since_improvement: usize) -> AnnealStatus {
+        AnnealStatus {
+            step: 100,
+            current_energy: best_energy,
+            best_energy,
+            steps_since_improvement,
+            direction: Direction::Minimize,
+        }
+    }
+
+    #[test]
+    fn target_energy() {
+        let mut condition = TargetEnergy::new(1.0);
+        assert_eq!(condition.check(&status(1.5, 0)), None);
+        assert_eq!(
+            condition.check(&status(1.0, 0)),
+            Some(StopReason::TargetEnergy)
+        );
+
+        let maximize = |best_energy| AnnealStatus {
+            direction: Direction::Maximize,
+            ..status(best_energy, 0)
+        };
+        assert_eq!(condition.check(&maximize(0.5)), None);
+        assert_eq!(
+            condition.check(&maximize(1.5)),
+            Some(StopReason::TargetEnergy)
+        );
+    }
+
+    #[test]
+    fn stagnation() {
+        let mut condition = Stagnation::new(10);
+        assert_eq!(condition.check(&status(0.0, 9)), None);
+        assert_eq!(
+            condition.check(&status(0.0, 10)),
+            Some(StopReason::Stagnation)
+        );
+    }
+
+    #[test]
+    fn cancellation() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut condition = Cancellation::new(flag.clone());
+        assert_eq!(condition.check(&status(0.0, 0)), None);
+        flag.store(true, Ordering::Relaxed);
+        assert_eq!(
+            condition.check(&status(0.0, 0)),
+            Some(StopReason::Cancelled)
+        );
+    }
+
+    #[test]
+    fn combinators() {
+        let mut or = TargetEnergy::new(1.0).or(Stagnation::new(10));
+        assert_eq!(or.check(&status(2.0, 0)), None);
+        assert_eq!(or.check(&status(2.0, 10)), Some(StopReason::Stagnation));
+        assert_eq!(or.check(&status(0.0, 10)), Some(StopReason::TargetEnergy));
+
+        let mut and = TargetEnergy::new(1.0).and(Stagnation::new(10));
+        assert_eq!(and.check(&status(0.0, 0)), None);
+        assert_eq!(and.check(&status(0.0, 10)), Some(StopReason::Stagnation));
+
+        let mut custom = |status: &AnnealStatus| status.step >= 100;
+        assert_eq!(custom.check(&status(0.0, 0)), Some(StopReason::Custom));
+    }
+}
@@ -0,0 +1,276 @@
+use rand::{Rng, SeedableRng};
+
+use crate::acceptance::{Acceptance, Metropolis};
+use crate::energy::Energy;
+use crate::{AnnealingState, Transition};
+
+/// Swap statistics of a pair of adjacent temperatures.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SwapStats {
+    pub attempts: usize,
+    pub accepts: usize,
+}
+
+impl SwapStats {
+    /// Accepted swaps per attempted swap, 0 when no swap was attempted
+    pub fn acceptance_rate(&self) -> f64 {
+        if self.attempts == 0 {
+            return 0.0;
+        }
+        self.accepts as f64 / self.attempts as f64
+    }
+}
+
+/// Temperatures from t_min to t_max in geometric progression.
+pub fn geometric_ladder(t_min: f64, t_max: f64, replicas: usize) -> Vec<f64> {
+    if replicas == 1 {
+        return vec![t_min];
+    }
+    (0..replicas)
+        .map(|i| t_min * (t_max / t_min).powf(i as f64 / (replicas - 1) as f64))
+        .collect()
+}
+
+/// Parallel tempering (replica exchange).
+/// Each replica is a Metropolis walk at a fixed temperature of the ladder.
+/// After every `swap_interval` steps, states of adjacent temperatures are swapped with
+/// probability min(1, exp((1/T_i - 1/T_j)(E_i - E_j))).
+pub struct ParallelTempering<S: AnnealingState> {
+    pub ctx: S::Context,
+    /// Temperature ladder, usually ascending
+    pub temperatures: Vec<f64>,
+    /// Current state at each temperature
+    pub replicas: Vec<S>,
+    /// Steps of each replica between swap attempts
+    pub swap_interval: usize,
+    /// Statistics of the pairs (temperatures[i], temperatures[i + 1])
+    pub swap_stats: Vec<SwapStats>,
+}
+
+impl<S: AnnealingState> ParallelTempering<S> {
+    /// Start every replica from a clone of the state.
+    pub fn new(state: S, ctx: S::Context, temperatures: Vec<f64>, swap_interval: usize) -> Self {
+        let replicas = vec![state; temperatures.len()];
+        Self::from_replicas(replicas, ctx, temperatures, swap_interval)
+    }
+
+    pub fn from_replicas(
+        replicas: Vec<S>,
+        ctx: S::Context,
+        temperatures: Vec<f64>,
+        swap_interval: usize,
+    ) -> Self {
+        assert_eq!(
+            replicas.len(),
+            temperatures.len(),
+            "one replica per temperature"
+        );
+        assert!(!replicas.is_empty(), "at least one replica");
+        Self {
+            ctx,
+            swap_stats: vec![SwapStats::default(); temperatures.len() - 1],
+            temperatures,
+            replicas,
+            swap_interval,
+        }
+    }
+
+    /// Run `rounds` rounds of walks followed by swap attempts and return the best state found.
+    /// Each replica uses its own random number generator seeded from `rng`.
+    pub fn run<G: Rng + SeedableRng>(&mut self, rng: &mut G, rounds: usize) -> S {
+        let mut walkers = self.walkers(rng);
+        for _ in 0..rounds {
+            for ((walker, rng), &temperature) in walkers.iter_mut().zip(&self.temperatures) {
+                walker.walk(rng, &self.ctx, temperature, self.swap_interval);
+            }
+            self.swap(rng, &mut walkers);
+        }
+        self.finish(walkers)
+    }
+
+    /// Same as `run` with the walks of the replicas running in parallel on the rayon thread pool.
+    /// The result is the same as `run` for the same `rng`.
+    #[cfg(feature = "rayon")]
+    pub fn run_parallel<G: Rng + SeedableRng + Send>(&mut self, rng: &mut G, rounds: usize) -> S
+    where
+        S: Send,
+        S::Context: Sync,
+        S::Energy: Send,
+    {
+        use rayon::prelude::*;
+
+        let mut walkers = self.walkers(rng);
+        for _ in 0..rounds {
+            let ctx = &self.ctx;
+            let swap_interval = self.swap_interval;
+            walkers
+                .par_iter_mut()
+                .zip(self.temperatures.par_iter())
+                .for_each(|((walker, rng), &temperature)| {
+                    walker.walk(rng, ctx, temperature, swap_interval)
+                });
+            self.swap(rng, &mut walkers);
+        }
+        self.finish(walkers)
+    }
+
+    fn walkers<G: Rng + SeedableRng>(&self, rng: &mut G) -> Vec<(Walker<S>, G)> {
+        self.replicas
+            .iter()
+            .map(|state| {
+                let walker = Walker::new(state.clone(), &self.ctx);
+                (walker, G::from_rng(&mut *rng).expect("seed a replica"))
+            })
+            .collect()
+    }
+
+    fn swap<G: Rng, W>(&mut self, rng: &mut G, walkers: &mut [(Walker<S>, W)]) {
+        for i in 0..walkers.len() - 1 {
+            let (t_i, t_j) = (self.temperatures[i], self.temperatures[i + 1]);
+            let (e_i, e_j) = (walkers[i].0.energy, walkers[i + 1].0.energy);
+            let p = rng.gen_range(0.0..=1.0);
+            self.swap_stats[i].attempts += 1;
+            if ((1.0 / t_i - 1.0 / t_j) * e_i.delta(e_j)).exp() >= p {
+                self.swap_stats[i].accepts += 1;
+                let (lower, upper) = walkers.split_at_mut(i + 1);
+                std::mem::swap(&mut lower[i].0.state, &mut upper[0].0.state);
+                std::mem::swap(&mut lower[i].0.energy, &mut upper[0].0.energy);
+            }
+        }
+    }
+
+    fn finish<G>(&mut self, walkers: Vec<(Walker<S>, G)>) -> S {
+        let mut best: Option<(S, S::Energy)> = None;
+        self.replicas.clear();
+        for (walker, _) in walkers {
+            self.replicas.push(walker.state);
+            if best
+                .as_ref()
+                .is_none_or(|(_, best_energy)| walker.best_energy < *best_energy)
+            {
+                best = Some((walker.best_state, walker.best_energy));
+            }
+        }
+        best.expect("at least one replica").0
+    }
+}
+
+/// State doing a Metropolis walk at a fixed temperature, keeping the best state it visited.
+#[derive(Clone)]
+pub(crate) struct Walker<S: AnnealingState> {
+    pub(crate) state: S,
+    pub(crate) energy: S::Energy,
+    pub(crate) best_state: S,
+    pub(crate) best_energy: S::Energy,
+}
+
+impl<S: AnnealingState> Walker<S> {
+    pub(crate) fn new(state: S, ctx: &S::Context) -> Self {
+        let energy = state.energy(ctx);
+        Self {
+            best_state: state.clone(),
+            state,
+            energy,
+            best_energy: energy,
+        }
+    }
+
+    /// Walk `steps` steps at the temperature.
+    pub(crate) fn walk<G: Rng>(
+        &mut self,
+        rng: &mut G,
+        ctx: &S::Context,
+        temperature: f64,
+        steps: usize,
+    ) {
+        for _ in 0..steps {
+            let prev_state = self.state.clone();
+            let op = S::Transition::choose(rng, ctx, &self.state);
+            if self.state.apply(ctx, &op).is_none() {
+                continue;
+            }
+            let new_energy = self.state.energy(ctx);
+            // energies relative to the best one, which Metropolis does not look at anyway
+            let accept = Metropolis.accept(
+                rng,
+                self.energy.delta(self.best_energy),
+                new_energy.delta(self.energy),
+                0.0,
+                temperature,
+            );
+            if accept {
+                self.energy = new_energy;
+                if new_energy < self.best_energy {
+                    self.best_energy = new_energy;
+                    self.best_state = self.state.clone();
+                }
+            } else {
+                self.state = prev_state;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+
+    use super::*;
+    use crate::tests::{QuadraticFunction, QuadraticFunctionState};
+
+    fn tempering() -> ParallelTempering<QuadraticFunctionState> {
+        ParallelTempering::new(
+            QuadraticFunctionState { x: 100.0 },
+            QuadraticFunction {
+                a: 1.0,
+                b: 10.0,
+                c: 30.0,
+            },
+            geometric_ladder(0.01, 1000.0, 6),
+            10,
+        )
+    }
+
+    #[test]
+    fn ladder() {
+        let ladder = geometric_ladder(1.0, 100.0, 3);
+        assert_eq!(ladder.len(), 3);
+        assert!((ladder[1] - 10.0).abs() < 1e-9);
+        assert!((ladder[2] - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn no_swap_attempted() {
+        let mut tempering = tempering();
+        tempering.run(&mut SmallRng::seed_from_u64(0), 0);
+        assert_eq!(tempering.swap_stats[0].acceptance_rate(), 0.0);
+    }
+
+    #[test]
+    fn solve_quadratic_function() {
+        let mut tempering = tempering();
+        let state = tempering.run(&mut SmallRng::seed_from_u64(0), 500);
+
+        assert!((state.x - (-5.0)).abs() < 0.1, "{:?}", state);
+        assert_eq!(tempering.replicas.len(), 6);
+        assert_eq!(tempering.swap_stats.len(), 5);
+        for stats in &tempering.swap_stats {
+            assert_eq!(stats.attempts, 500);
+            assert!(stats.accepts > 0);
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_matches_sequential() {
+        let mut sequential = tempering();
+        let mut parallel = tempering();
+        let sequential_state = sequential.run(&mut SmallRng::seed_from_u64(1), 100);
+        let parallel_state = parallel.run_parallel(&mut SmallRng::seed_from_u64(1), 100);
+
+        assert_eq!(sequential_state.x, parallel_state.x);
+        for (s, p) in sequential.swap_stats.iter().zip(&parallel.swap_stats) {
+            assert_eq!(s.accepts, p.accepts);
+        }
+    }
+}
@@ -1,4 +1,5 @@
-use crate::{AnnealingStateBack, AnnealingStatePeeking, InitialState, Transition};
+use crate::energy::Energy;
+use crate::{AnnealingStateBack, AnnealingStatePeeking, Transition};
 
 /// Semi auto test the implementation of AnnealingStatePeeking.
 pub fn run_peeking_and_check<S: AnnealingStatePeeking>(
@@ -15,9 +16,8 @@ pub fn run_peeking_and_check<S: AnnealingStatePeeking>(
         if let Some(new_energy) = new_energy {
             state.apply(context, &transition);
             let new_energy_ref = state.energy(context);
-            assert_eq!(
-                round_decimal_places(new_energy.into(), decimal_places),
-                round_decimal_places(new_energy_ref.into(), decimal_places),
+            assert!(
+                same_energy(new_energy, new_energy_ref, decimal_places),
                 "transition: {:?}, new_energy: {:?}, new_energy_ref: {:?}",
                 transition,
                 new_energy,
@@ -43,20 +43,33 @@ pub fn run_back_and_check<S: AnnealingStateBack>(
             let next_energy = state.energy(context);
             state.back(context, &restore);
             let current_energy = state.energy(context);
-            assert_eq!(
-                round_decimal_places(current_energy_ref.into(), decimal_places),
-                round_decimal_places(current_energy.into(), decimal_places)
+            assert!(
+                same_energy(current_energy_ref, current_energy, decimal_places),
+                "transition: {:?}, current_energy: {:?}, current_energy_ref: {:?}",
+                transition,
+                current_energy,
+                current_energy_ref
             );
             state.apply(context, &transition);
             let next_energy_ref = state.energy(context);
-            assert_eq!(
-                round_decimal_places(next_energy_ref.into(), decimal_places),
-                round_decimal_places(next_energy.into(), decimal_places)
+            assert!(
+                same_energy(next_energy_ref, next_energy, decimal_places),
+                "transition: {:?}, next_energy: {:?}, next_energy_ref: {:?}",
+                transition,
+                next_energy,
+                next_energy_ref
             );
         }
     }
 }
 
+// Whether the energies are equal up to the decimal places.
+// The difference is compared rather than the rounded energies, so that integer energies are compared exactly
+// even when they are too large for f64.
+fn same_energy<E: Energy>(energy: E, energy_ref: E, decimal_places: usize) -> bool {
+    round_decimal_places(energy.delta(energy_ref), decimal_places) == 0.0
+}
+
 // Round a floating point number to a specified number of decimal places.
 fn round_decimal_places(value: f64, decimal_places: usize) -> f64 {
     format!("{:.1$}", value, decimal_places).parse().unwrap()